@@ -0,0 +1,194 @@
+use std::cmp::Reverse;
+use std::fmt::Display;
+
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+
+use super::board::Connect4;
+use super::engine::Engine;
+use super::position::mirror_column;
+use super::position::Position;
+use super::strategy::Move;
+use super::symbol::Symbol;
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// Searches each column of `symbol`, `max_depth` moves ahead, and returns their moves
+    /// best first, with their principal variations.
+    ///
+    /// Unlike the search of the best move, which only proves that the other columns are not
+    /// better, each column gets its exact score. The statistics of `engine` add up the
+    /// searches of all the columns.
+    ///
+    /// On a symmetric board, the columns of the right half get the moves of their mirror
+    /// images without being searched.
+    pub fn analyze(&mut self, engine: &mut Engine, symbol: Symbol, max_depth: i32) -> Vec<Move> {
+        engine.stats = Default::default();
+        let symmetric = self.is_symmetric();
+        let mut moves: Vec<Move> = vec![];
+        for column in 0..WIDTH as i32 {
+            let mirror = mirror_column(column, WIDTH, true);
+            if symmetric && mirror < column {
+                if let Some(mirror_move) = moves.iter().find(|next_move| next_move.column == mirror)
+                {
+                    let mirror_move = Move {
+                        column,
+                        score: mirror_move.score,
+                        outcome: match mirror_move.outcome {
+                            GameOutcome::Win { winner, line } => GameOutcome::Win {
+                                winner,
+                                line: line.map(|point| {
+                                    Point::new(mirror_column(point.x, WIDTH, true), point.y)
+                                }),
+                            },
+                            outcome => outcome,
+                        },
+                        pv: mirror_move
+                            .pv
+                            .iter()
+                            .map(|column| mirror_column(*column, WIDTH, true))
+                            .collect(),
+                    };
+                    moves.push(mirror_move);
+                }
+                continue;
+            }
+            let Some(play_column) = self.play_column(symbol, column) else {
+                continue;
+            };
+            let mut next_move = Move {
+                column,
+                score: play_column.delta_score,
+                outcome: play_column.outcome,
+                pv: vec![column],
+            };
+            if !play_column.outcome.is_over() && max_depth > 0 {
+                if let Some(reply) = self.search(engine, symbol.other(), max_depth - 1) {
+                    next_move.score -= reply.score;
+                    next_move.pv.extend(reply.pv);
+                }
+            }
+            play_column.undo(self);
+            moves.push(next_move);
+        }
+        moves.sort_by_key(|next_move| Reverse(next_move.score));
+        moves
+    }
+
+    /// The board, as X and O, next to `moves` with their scores and principal variations.
+    pub fn view_analysis<'t>(&'t self, moves: &'t [Move]) -> AnalysisView<'t, WIDTH, HEIGHT> {
+        AnalysisView { board: self, moves }
+    }
+}
+
+/// Display of the moves of `Connect4::analyze` next to the board.
+pub struct AnalysisView<'t, const WIDTH: usize, const HEIGHT: usize> {
+    board: &'t Connect4<WIDTH, HEIGHT>,
+    moves: &'t [Move],
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Display for AnalysisView<'_, WIDTH, HEIGHT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let board = self.board.view_as_xo().to_string();
+        let board: Vec<&str> = board.lines().collect();
+        let board_width = board.iter().map(|line| line.len()).max().unwrap_or(0);
+        let scores: Vec<String> = self
+            .moves
+            .iter()
+            .map(|next_move| format!("{:+}", next_move.score))
+            .collect();
+        let score_width = scores.iter().map(String::len).max().unwrap_or(0).max(5);
+        let analysis: Vec<String> =
+            std::iter::once(format!("Column | {:>score_width$} | Line", "Score"))
+                .chain(self.moves.iter().zip(&scores).map(|(next_move, score)| {
+                    let line: Vec<String> = next_move
+                        .pv
+                        .iter()
+                        .map(|column| (column + 1).to_string())
+                        .collect();
+                    format!(
+                        "{:>6} | {score:>score_width$} | {}",
+                        next_move.column + 1,
+                        line.join(" ")
+                    )
+                }))
+                .collect();
+        for i in 0..board.len().max(analysis.len()) {
+            let board = board.get(i).copied().unwrap_or_default();
+            match analysis.get(i) {
+                Some(analysis) => writeln!(f, "{board:board_width$}    {analysis}")?,
+                None => writeln!(f, "{board}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for (column, symbol) in [(3, Symbol::Red), (3, Symbol::Yellow), (2, Symbol::Red)] {
+            board.play_column(symbol, column);
+        }
+        let moves = board.analyze(&mut Engine::new(1 << 16), Symbol::Yellow, 4);
+        let best = board
+            .search(&mut Engine::new(1 << 16), Symbol::Yellow, 4)
+            .unwrap();
+        assert_eq!(7, moves.len());
+        assert_eq!((best.column, best.score), (moves[0].column, moves[0].score));
+        assert!(moves.windows(2).all(|pair| pair[0].score >= pair[1].score));
+        for next_move in &moves {
+            assert_eq!(next_move.column, next_move.pv[0]);
+            assert!(next_move.pv.len() <= 5);
+        }
+    }
+
+    #[test]
+    fn symmetric() {
+        let mut board = Connect4::<7, 6>::new(4);
+        board.play_column(Symbol::Red, 3);
+        let moves = board.analyze(&mut Engine::new(1 << 16), Symbol::Yellow, 3);
+        assert_eq!(7, moves.len());
+        for next_move in moves.iter().filter(|next_move| next_move.column != 3) {
+            let mirror = moves
+                .iter()
+                .find(|mirror| mirror.column == 6 - next_move.column)
+                .unwrap();
+            assert_eq!(next_move.score, mirror.score);
+            assert_eq!(
+                next_move.pv,
+                mirror
+                    .pv
+                    .iter()
+                    .map(|column| 6 - column)
+                    .collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn view_analysis() {
+        let mut board = Connect4::<5, 4>::new(3);
+        board.play_column(Symbol::Red, 2);
+        board.play_column(Symbol::Yellow, 2);
+        let moves = board.analyze(&mut Engine::new(1 << 16), Symbol::Red, 1);
+        println!("{}", board.view_analysis(&moves));
+        assert_eq!(
+            r#"
+    | 1 | 2 | 3 | 4 | 5     Column |  Score | Line
+----+---+---+---+---+---         2 | -20000 | 2 2
+ A  |   |   |   |   |            4 | -20000 | 4 4
+----+---+---+---+---+---         1 | -40000 | 1 3
+ B  |   |   |   |   |            3 | -40000 | 3 2
+----+---+---+---+---+---         5 | -40000 | 5 3
+ C  |   |   | O |   |   
+----+---+---+---+---+---
+ D  |   |   | X |   |   
+"#,
+            format!("\n{}", board.view_analysis(&moves))
+        );
+    }
+}
@@ -0,0 +1,463 @@
+use crate::shared::board::board_all_points::BoardAllPoints;
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::board::board_size::BoardSize;
+use crate::shared::board::board_to_string::AsChar;
+use crate::shared::board::board_view::AsBoardView;
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+
+use super::board::Connect4;
+use super::cell::Connect4Cell;
+use super::eval::EvalParams;
+use super::game::intersecting_lines;
+use super::game::PlayColumn;
+use super::position::Position;
+use super::symbol::Symbol;
+use super::zobrist;
+
+/// Connect4 board stored as two bitboards: the tokens of the player to move, and all the
+/// tokens.
+///
+/// Each column uses `HEIGHT + 1` bits, from the bottom row up to a sentinel row that is
+/// always empty, so that alignments can't wrap from one column to the next. Hence boards up
+/// to 7x6 or 8x7 fit in a `u64`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitBoard<const WIDTH: usize, const HEIGHT: usize> {
+    /// How many tokens in a row do you need to win.
+    pub winning_tokens: i32,
+
+    /// Weights of the scores of the lines.
+    pub params: EvalParams,
+
+    /// Tokens of the player to move.
+    pub(super) current: u64,
+
+    /// All the tokens.
+    pub(super) mask: u64,
+
+    /// The player whose tokens are in `current`.
+    to_move: Symbol,
+
+    /// Zobrist hash of the board, the same as the hash of the equivalent `Connect4` board.
+    hash: u64,
+
+    /// Zobrist hash of the mirror image of the board.
+    mirror_hash: u64,
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> BitBoard<WIDTH, HEIGHT> {
+    /// Whether a `WIDTH` x `HEIGHT` board fits in a bitboard.
+    pub const fn fits() -> bool {
+        WIDTH * (HEIGHT + 1) <= u64::BITS as usize
+    }
+
+    pub fn new(winning_tokens: i32, to_move: Symbol) -> Self {
+        assert!(
+            Self::fits(),
+            "{WIDTH}x{HEIGHT} board doesn't fit in a bitboard"
+        );
+        Self {
+            winning_tokens,
+            params: Default::default(),
+            current: 0,
+            mask: 0,
+            to_move,
+            hash: 0,
+            mirror_hash: 0,
+        }
+    }
+
+    /// Copies `board`, or returns `None` if it doesn't fit in a bitboard.
+    pub fn from_board(board: &Connect4<WIDTH, HEIGHT>, to_move: Symbol) -> Option<Self> {
+        if !Self::fits() {
+            return None;
+        }
+        let mut result = Self::new(board.winning_tokens, to_move);
+        result.params = board.params();
+        for point in board.all_points() {
+            let symbol = board.get(&point).symbol;
+            if symbol != Symbol::Empty {
+                result.mask |= Self::bit(&point);
+                if symbol == to_move {
+                    result.current |= Self::bit(&point);
+                }
+            }
+        }
+        result.hash = board.hash();
+        result.mirror_hash = board.mirror_hash();
+        Some(result)
+    }
+
+    #[cfg(test)]
+    pub fn view_as_xo(
+        &self,
+    ) -> crate::shared::board::board_view::BoardView<'_, Self, impl Fn(&Connect4Cell) -> &char>
+    {
+        self.as_view(Connect4Cell::as_xo)
+    }
+
+    /// Unique key of the tokens, whatever the colour of the player to move.
+    ///
+    /// Adding the tokens of the player to move to all the tokens carries them above the
+    /// top token of their column.
+    pub(super) fn key(&self) -> u64 {
+        self.current + self.mask
+    }
+
+    /// Mirrors `bits` laid out like the tokens of a bitboard, the first column becoming the
+    /// last one.
+    pub(super) fn mirror(bits: u64) -> u64 {
+        let column_bits = u64::MAX >> (u64::BITS as usize - (HEIGHT + 1));
+        (0..WIDTH).fold(0, |mirror, column| {
+            let bits = (bits >> (column * (HEIGHT + 1))) & column_bits;
+            mirror | bits << ((WIDTH - 1 - column) * (HEIGHT + 1))
+        })
+    }
+
+    fn bit(point: &Point) -> u64 {
+        1 << (point.x as usize * (HEIGHT + 1) + HEIGHT - 1 - point.y as usize)
+    }
+
+    fn point(bit: u64) -> Point {
+        let index = bit.trailing_zeros() as usize;
+        Point::new(
+            (index / (HEIGHT + 1)) as i32,
+            (HEIGHT - 1 - index % (HEIGHT + 1)) as i32,
+        )
+    }
+
+    fn column_mask(column: i32) -> u64 {
+        ((1 << HEIGHT) - 1) << (column as usize * (HEIGHT + 1))
+    }
+
+    fn bottom_mask(column: i32) -> u64 {
+        1 << (column as usize * (HEIGHT + 1))
+    }
+
+    /// Whether `tokens` contains `winning_tokens` in a row.
+    ///
+    /// Shifting the tokens by one cell in a direction and intersecting them with the
+    /// original ones leaves the tokens followed by another one in that direction, and so on.
+    pub(super) fn has_alignment(&self, tokens: u64) -> bool {
+        Self::aligned(self.winning_tokens as usize, tokens)
+    }
+
+    /// Whether `tokens` contains `winning_tokens` in a row, like `has_alignment`.
+    pub(super) fn aligned(winning_tokens: usize, tokens: u64) -> bool {
+        // Vertical, horizontal and both diagonals.
+        [1, HEIGHT + 1, HEIGHT, HEIGHT + 2].iter().any(|shift| {
+            (1..winning_tokens).fold(tokens, |aligned, i| {
+                aligned & tokens.checked_shr((i * shift) as u32).unwrap_or(0)
+            }) != 0
+        })
+    }
+
+    /// Whether neither `tokens` nor the other tokens of `mask` can be completed into an
+    /// alignment with the empty cells.
+    pub(super) fn dead(winning_tokens: usize, tokens: u64, mask: u64) -> bool {
+        let board = Self::board_mask();
+        !Self::aligned(winning_tokens, board & !(mask ^ tokens))
+            && !Self::aligned(winning_tokens, board & !tokens)
+    }
+
+    /// All the cells of the board, without the sentinel row.
+    fn board_mask() -> u64 {
+        (0..WIDTH as i32).fold(0, |board, column| board | Self::column_mask(column))
+    }
+
+    /// First and last cells of a line of `tokens` going through `position`, if any.
+    fn full_line(&self, tokens: u64, position: Point) -> Option<[Point; 2]> {
+        intersecting_lines(self, position, self.winning_tokens)
+            .map(|line| line.collect::<Vec<_>>())
+            .find(|line| line.iter().all(|point| tokens & Self::bit(point) != 0))
+            .map(|line| [line[0], line[line.len() - 1]])
+    }
+
+    /// Score of `tokens` on the lines going through `played`, like
+    /// `Connect4::eval_position`.
+    ///
+    /// In each direction, the line of `winning_tokens` cells starting at the token played is
+    /// shifted back one cell at a time. Cells shifted out of the board or onto the sentinel
+    /// row are lost, so only the lines that fit keep all their cells.
+    fn eval_position(&self, tokens: u64, played: u64) -> i32 {
+        let winning_tokens = self.winning_tokens.max(0) as u32;
+        let others = self.mask ^ tokens;
+        let board = Self::board_mask();
+        let index = played.trailing_zeros();
+        let score: i64 = [1, HEIGHT + 1, HEIGHT, HEIGHT + 2]
+            .into_iter()
+            .flat_map(|shift| {
+                let shift = shift as u32;
+                let line = (0..winning_tokens).fold(0u128, |line, i| {
+                    line | 1u128.checked_shl(i * shift).unwrap_or(0)
+                }) << index;
+                (0..winning_tokens).map(move |i| {
+                    let line = line.checked_shr(i * shift).unwrap_or(0) as u64 & board;
+                    if line.count_ones() != winning_tokens || line & others != 0 {
+                        0
+                    } else {
+                        let matches = (line & tokens).count_ones() as i32;
+                        self.params.line_score(self.winning_tokens, matches)
+                    }
+                })
+            })
+            .sum();
+        score.clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Position for BitBoard<WIDTH, HEIGHT> {
+    fn play_column(&mut self, symbol: Symbol, column: i32) -> Option<PlayColumn> {
+        if !self.is_valid(&Point::new(column, 0))
+            || self.mask & Self::bit(&Point::new(column, 0)) != 0
+        {
+            return None;
+        }
+        if symbol != self.to_move {
+            self.current ^= self.mask;
+            self.to_move = symbol;
+        }
+        let played = (self.mask + Self::bottom_mask(column)) & Self::column_mask(column);
+        self.current ^= self.mask;
+        self.mask |= played;
+        self.to_move = symbol.other();
+
+        let position = Self::point(played);
+        self.hash ^= zobrist::key(position, symbol);
+        self.mirror_hash ^= zobrist::mirror_key(WIDTH as i32, position, symbol);
+
+        // A line going through the token just played can't score for the other player.
+        let tokens = self.current ^ self.mask;
+        let full_line = if self.has_alignment(tokens) {
+            self.full_line(tokens, position)
+        } else {
+            None
+        };
+        let outcome = match full_line {
+            Some(line) => GameOutcome::Win {
+                winner: symbol,
+                line,
+            },
+            None if self.mask.count_ones() as usize == WIDTH * HEIGHT => GameOutcome::Draw,
+            None => GameOutcome::Ongoing,
+        };
+        Some(PlayColumn {
+            delta_score: self.eval_position(tokens, played),
+            outcome,
+            position,
+        })
+    }
+
+    fn undo_column(&mut self, play_column: &PlayColumn) {
+        let played = Self::bit(&play_column.position);
+        debug_assert!(self.mask & played != 0);
+        let (symbol, tokens) = if self.current & played != 0 {
+            (self.to_move, self.current)
+        } else {
+            (self.to_move.other(), self.current ^ self.mask)
+        };
+        self.mask ^= played;
+        self.current = tokens ^ played;
+        self.to_move = symbol;
+        self.hash ^= zobrist::key(play_column.position, symbol);
+        self.mirror_hash ^= zobrist::mirror_key(WIDTH as i32, play_column.position, symbol);
+    }
+
+    fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    fn mirror_hash(&self) -> u64 {
+        self.mirror_hash
+    }
+
+    fn is_dead(&self) -> bool {
+        Self::dead(self.winning_tokens as usize, self.current, self.mask)
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> BoardSize for BitBoard<WIDTH, HEIGHT> {
+    fn width(&self) -> i32 {
+        WIDTH as i32
+    }
+
+    fn height(&self) -> i32 {
+        HEIGHT as i32
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> BoardGet for BitBoard<WIDTH, HEIGHT> {
+    type Value = Connect4Cell;
+    fn get(&self, point: &Point) -> &Connect4Cell {
+        let bit = Self::bit(point);
+        if self.mask & bit == 0 {
+            &Connect4Cell {
+                symbol: Symbol::Empty,
+            }
+        } else if (self.current & bit != 0) == (self.to_move == Symbol::Red) {
+            &Connect4Cell {
+                symbol: Symbol::Red,
+            }
+        } else {
+            &Connect4Cell {
+                symbol: Symbol::Yellow,
+            }
+        }
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize, CellView, ViewFn> AsBoardView<CellView, ViewFn>
+    for BitBoard<WIDTH, HEIGHT>
+where
+    ViewFn: Fn(&Connect4Cell) -> &CellView,
+    CellView: AsChar,
+{
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> std::fmt::Display for BitBoard<WIDTH, HEIGHT> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.as_view(|cell| cell).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fits() {
+        assert!(BitBoard::<7, 6>::fits());
+        assert!(BitBoard::<8, 7>::fits());
+        assert!(!BitBoard::<9, 7>::fits());
+        assert!(BitBoard::<5, 4>::from_board(&Connect4::new(3), Symbol::Red).is_some());
+        assert!(BitBoard::<8, 8>::from_board(&Connect4::new(4), Symbol::Red).is_none());
+    }
+
+    #[test]
+    fn same_as_connect4() {
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut bitboard = BitBoard::<7, 6>::new(4, Symbol::Red);
+        let mut symbol = Symbol::Red;
+        let mut plays = vec![];
+        for column in [
+            3, 3, 4, 2, 2, 5, 1, 1, 6, 6, 6, 6, 6, 6, 6, 0, 0, 4, 4, 4, 5, 5, 1,
+        ] {
+            let expected = board.play_column(symbol, column);
+            let actual = bitboard.play_column(symbol, column);
+            assert_eq!(expected, actual, "\n{board}");
+            assert_eq!(board.hash(), bitboard.hash());
+            assert_eq!(board.mirror_hash(), bitboard.mirror_hash());
+            assert_eq!(format!("{board}"), format!("{bitboard}"));
+            plays.extend(actual);
+            symbol = symbol.other();
+        }
+        assert_eq!(bitboard, BitBoard::from_board(&board, symbol).unwrap());
+
+        for play_column in plays.iter().rev() {
+            play_column.undo(&mut bitboard);
+        }
+        assert_eq!(BitBoard::new(4, Symbol::Red), bitboard);
+    }
+
+    #[test]
+    fn same_score_as_connect4() {
+        fn check<const WIDTH: usize, const HEIGHT: usize>(winning_tokens: i32) {
+            let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+            let mut bitboard = BitBoard::<WIDTH, HEIGHT>::new(winning_tokens, Symbol::Red);
+            let mut symbol = Symbol::Red;
+            for column in (0..WIDTH * HEIGHT).map(|i| ((i * 3 + i / WIDTH) % WIDTH) as i32) {
+                let expected = board.play_column(symbol, column);
+                let actual = bitboard.play_column(symbol, column);
+                // Both find a line when several are completed at once, not always the same.
+                let score = |play_column: &Option<PlayColumn>| {
+                    play_column
+                        .as_ref()
+                        .map(|play_column| (play_column.delta_score, play_column.outcome.is_over()))
+                };
+                assert_eq!(score(&expected), score(&actual), "\n{board}");
+                if score(&expected).is_none_or(|(_, over)| over) {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+        }
+        check::<5, 4>(3);
+        check::<7, 6>(5);
+        check::<8, 7>(4);
+        check::<8, 7>(8);
+        check::<4, 4>(6);
+    }
+
+    #[test]
+    fn mirror() {
+        let mut board = BitBoard::<7, 6>::new(4, Symbol::Red);
+        let mut mirror = BitBoard::<7, 6>::new(4, Symbol::Red);
+        let mut symbol = Symbol::Red;
+        for column in [3, 2, 2, 0, 6, 6, 5] {
+            board.play_column(symbol, column);
+            mirror.play_column(symbol, 6 - column);
+            symbol = symbol.other();
+        }
+        assert_ne!(board.key(), mirror.key());
+        assert_eq!(mirror.key(), BitBoard::<7, 6>::mirror(board.key()));
+        assert_eq!(board.key(), BitBoard::<7, 6>::mirror(mirror.key()));
+    }
+
+    #[test]
+    fn same_symbol_twice() {
+        let mut bitboard = BitBoard::<5, 4>::new(3, Symbol::Red);
+        let play_red = bitboard.play_column(Symbol::Red, 1).unwrap();
+        let play_red_again = bitboard.play_column(Symbol::Red, 2).unwrap();
+        let play_yellow = bitboard.play_column(Symbol::Yellow, 2).unwrap();
+        assert_eq!(Symbol::Red, bitboard.get(&Point::new(1, 3)).symbol);
+        assert_eq!(Symbol::Red, bitboard.get(&Point::new(2, 3)).symbol);
+        assert_eq!(Symbol::Yellow, bitboard.get(&Point::new(2, 2)).symbol);
+
+        play_yellow.undo(&mut bitboard);
+        play_red_again.undo(&mut bitboard);
+        play_red.undo(&mut bitboard);
+        assert_eq!(BitBoard::new(3, Symbol::Red), bitboard);
+    }
+
+    #[test]
+    fn has_alignment() {
+        let bitboard = BitBoard::<7, 6>::new(4, Symbol::Red);
+        let tokens = |points: &[(i32, i32)]| {
+            points.iter().fold(0, |tokens, (x, y)| {
+                tokens | BitBoard::<7, 6>::bit(&Point::new(*x, *y))
+            })
+        };
+        assert!(bitboard.has_alignment(tokens(&[(0, 5), (0, 4), (0, 3), (0, 2)])));
+        assert!(bitboard.has_alignment(tokens(&[(3, 5), (4, 5), (5, 5), (6, 5)])));
+        assert!(bitboard.has_alignment(tokens(&[(0, 5), (1, 4), (2, 3), (3, 2)])));
+        assert!(bitboard.has_alignment(tokens(&[(6, 5), (5, 4), (4, 3), (3, 2)])));
+        assert!(!bitboard.has_alignment(tokens(&[(0, 5), (0, 4), (0, 3), (1, 4)])));
+
+        // Lines wrapping from one column to the next.
+        assert!(!bitboard.has_alignment(tokens(&[(0, 1), (0, 0), (1, 5), (1, 4)])));
+        assert!(!bitboard.has_alignment(tokens(&[(0, 2), (1, 1), (2, 0), (3, 5)])));
+        assert!(!bitboard.has_alignment(tokens(&[(0, 3), (1, 4), (2, 5), (3, 0)])));
+    }
+
+    #[test]
+    fn view_as_xo() {
+        let mut bitboard = BitBoard::<5, 4>::new(3, Symbol::Red);
+        bitboard.play_column(Symbol::Red, 1);
+        bitboard.play_column(Symbol::Yellow, 1);
+        bitboard.play_column(Symbol::Red, 4);
+        assert_eq!(
+            r#"
+    | 1 | 2 | 3 | 4 | 5 
+----+---+---+---+---+---
+ A  |   |   |   |   |   
+----+---+---+---+---+---
+ B  |   |   |   |   |   
+----+---+---+---+---+---
+ C  |   | O |   |   |   
+----+---+---+---+---+---
+ D  |   | X |   |   | X 
+"#,
+            format!("\n{}", bitboard.view_as_xo())
+        );
+    }
+}
@@ -3,11 +3,12 @@ use crate::shared::board::board_generate::BoardGenerate;
 use crate::shared::board::board_get_set::BoardGet;
 use crate::shared::board::board_get_set::BoardSet;
 use crate::shared::board::board_size::BoardSize;
+use crate::shared::board::board_size::Size;
 use crate::shared::board::board_view::AsBoardView;
 use crate::shared::board::board_view::BoardView;
 
 use super::cell::Connect4Cell;
-use super::symbol::Symbol;
+use super::tally::Tally;
 
 pub struct Connect4<const WIDTH: usize, const HEIGHT: usize> {
     /// How many tokens in a row do you need to win.
@@ -15,12 +16,27 @@ pub struct Connect4<const WIDTH: usize, const HEIGHT: usize> {
 
     /// The Connect4 game board.
     board: ArrayBoard<WIDTH, HEIGHT, Connect4Cell>,
+
+    /// Zobrist hash of the board, kept up to date by `play_column` and `PlayColumn::undo`.
+    pub(super) hash: u64,
+
+    /// Zobrist hash of the mirror image of the board, kept up to date like `hash`.
+    pub(super) mirror_hash: u64,
+
+    /// Tokens in each line of the board, kept up to date by `play_column` and
+    /// `PlayColumn::undo`.
+    pub(super) tally: Tally,
 }
 
 impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
     pub fn new(winning_tokens: i32) -> Self {
         Self {
             winning_tokens,
+            tally: Tally::new(
+                Size::new(WIDTH as i32, HEIGHT as i32),
+                winning_tokens,
+                Default::default(),
+            ),
             ..Default::default()
         }
     }
@@ -28,11 +44,7 @@ impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
     pub fn view_as_xo(
         &self,
     ) -> BoardView<ArrayBoard<WIDTH, HEIGHT, Connect4Cell>, impl Fn(&Connect4Cell) -> &char> {
-        self.board.as_view(|cell| match cell.symbol {
-            Symbol::Empty => &' ',
-            Symbol::Red => &'X',
-            Symbol::Yellow => &'O',
-        })
+        self.board.as_view(Connect4Cell::as_xo)
     }
 }
 
@@ -41,6 +53,13 @@ impl<const WIDTH: usize, const HEIGHT: usize> Default for Connect4<WIDTH, HEIGHT
         Self {
             winning_tokens: Default::default(),
             board: ArrayBoard::<WIDTH, HEIGHT, Connect4Cell>::generate(|_point| Default::default()),
+            hash: 0,
+            mirror_hash: 0,
+            tally: Tally::new(
+                Size::new(WIDTH as i32, HEIGHT as i32),
+                0,
+                Default::default(),
+            ),
         }
     }
 }
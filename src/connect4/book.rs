@@ -0,0 +1,366 @@
+use std::collections::HashSet;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use super::bitboard::BitBoard;
+use super::board::Connect4;
+use super::engine::Engine;
+use super::position::mirror_column;
+use super::position::Position;
+use super::symbol::Symbol;
+
+/// First bytes of a book file.
+const MAGIC: &[u8; 4] = b"C4BK";
+
+/// Version of the book file format.
+const VERSION: u8 = 1;
+
+/// Best moves of the first positions of a game, searched ahead of time.
+///
+/// Positions are keyed by their tokens, from the point of view of the player to move, so
+/// that a position and its mirror image share the same entry.
+///
+/// A book file is made of a header followed by the entries sorted by key:
+/// - `C4BK`, the format version, the width, height and winning tokens of the board, how
+///   many moves the book covers and how deep its positions were searched, one byte each,
+/// - the number of entries, as a little-endian `u32`,
+/// - for each entry, the key as a little-endian `u64`, the column as one byte and the score
+///   as a little-endian `i32`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Book {
+    width: u8,
+    height: u8,
+    winning_tokens: u8,
+
+    /// The book has the positions with fewer tokens than `plies`.
+    plies: u8,
+
+    /// Depth of the search of each position.
+    depth: u8,
+
+    entries: Vec<BookEntry>,
+}
+
+/// Best move of a position, for the orientation of the position given by its key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BookEntry {
+    key: u64,
+    column: u8,
+    score: i32,
+}
+
+/// Move read from the book.
+#[derive(Debug, PartialEq, Eq)]
+pub struct BookMove {
+    pub column: i32,
+    pub score: i32,
+}
+
+impl Book {
+    /// Searches all the positions of fewer than `plies` tokens that Red can reach by
+    /// playing first, `depth` moves ahead.
+    ///
+    /// `progress` is called with the number of positions searched so far.
+    pub fn build<const WIDTH: usize, const HEIGHT: usize>(
+        engine: &mut Engine,
+        winning_tokens: i32,
+        plies: i32,
+        depth: i32,
+        mut progress: impl FnMut(usize),
+    ) -> Self {
+        assert!(
+            BitBoard::<WIDTH, HEIGHT>::fits(),
+            "{WIDTH}x{HEIGHT} board doesn't fit in a bitboard"
+        );
+        let mut book = Self {
+            width: WIDTH as u8,
+            height: HEIGHT as u8,
+            winning_tokens: winning_tokens as u8,
+            plies: plies as u8,
+            depth: depth as u8,
+            entries: vec![],
+        };
+        let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+        let mut keys = HashSet::new();
+        book.build_impl(
+            engine,
+            &mut board,
+            Symbol::Red,
+            plies,
+            &mut keys,
+            &mut progress,
+        );
+        book.entries.sort_by_key(|entry| entry.key);
+        book
+    }
+
+    fn build_impl<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        engine: &mut Engine,
+        board: &mut Connect4<WIDTH, HEIGHT>,
+        symbol: Symbol,
+        plies: i32,
+        keys: &mut HashSet<u64>,
+        progress: &mut impl FnMut(usize),
+    ) {
+        if plies == 0 {
+            return;
+        }
+        let (key, mirrored) = Self::key(board, symbol).unwrap();
+        if !keys.insert(key) {
+            return;
+        }
+        if let Some(next_move) = board.search(engine, symbol, self.depth as i32) {
+            self.entries.push(BookEntry {
+                key,
+                column: mirror_column(next_move.column, WIDTH, mirrored) as u8,
+                score: next_move.score,
+            });
+            progress(self.entries.len());
+        }
+        for column in 0..WIDTH as i32 {
+            if let Some(play_column) = board.play_column(symbol, column) {
+                if !play_column.outcome.is_over() {
+                    self.build_impl(engine, board, symbol.other(), plies - 1, keys, progress);
+                }
+                play_column.undo(board);
+            }
+        }
+    }
+
+    /// Best move of `board` for `symbol`, if the book has it.
+    pub fn get<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        board: &Connect4<WIDTH, HEIGHT>,
+        symbol: Symbol,
+    ) -> Option<BookMove> {
+        if (
+            self.width as usize,
+            self.height as usize,
+            self.winning_tokens as i32,
+        ) != (WIDTH, HEIGHT, board.winning_tokens)
+        {
+            return None;
+        }
+        let (key, mirrored) = Self::key(board, symbol)?;
+        let index = self
+            .entries
+            .binary_search_by_key(&key, |entry| entry.key)
+            .ok()?;
+        let entry = self.entries[index];
+        Some(BookMove {
+            column: mirror_column(entry.column as i32, WIDTH, mirrored),
+            score: entry.score,
+        })
+    }
+
+    /// Depth of the search of each position.
+    pub fn depth(&self) -> i32 {
+        self.depth as i32
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Key of `board` for `symbol` to move, the smallest of the keys of the board and of its
+    /// mirror image, and whether it's the key of the mirror image.
+    fn key<const WIDTH: usize, const HEIGHT: usize>(
+        board: &Connect4<WIDTH, HEIGHT>,
+        symbol: Symbol,
+    ) -> Option<(u64, bool)> {
+        let key = BitBoard::from_board(board, symbol)?.key();
+        let mirror = BitBoard::<WIDTH, HEIGHT>::mirror(key);
+        Some(if mirror < key {
+            (mirror, true)
+        } else {
+            (key, false)
+        })
+    }
+
+    pub fn load<const WIDTH: usize, const HEIGHT: usize>(
+        path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        Self::read::<WIDTH, HEIGHT>(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads a book of a `WIDTH` x `HEIGHT` board.
+    pub fn read<const WIDTH: usize, const HEIGHT: usize>(
+        mut reader: impl Read,
+    ) -> Result<Self, Error> {
+        let mut header = [0; 10];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(Error::NotABook);
+        }
+        if header[4] != VERSION {
+            return Err(Error::UnsupportedVersion(header[4]));
+        }
+        let [width, height, winning_tokens, plies, depth] = [5, 6, 7, 8, 9].map(|i| header[i]);
+        if (width as usize, height as usize) != (WIDTH, HEIGHT) {
+            return Err(Error::WrongBoard { width, height });
+        }
+        let mut len = [0; 4];
+        reader.read_exact(&mut len)?;
+        let len = u32::from_le_bytes(len) as usize;
+
+        // Not trusting `len` to allocate memory upfront.
+        let mut entries = Vec::with_capacity(len.min(1 << 20));
+        let mut entry = [0; 13];
+        for _ in 0..len {
+            reader.read_exact(&mut entry)?;
+            entries.push(BookEntry {
+                key: u64::from_le_bytes(entry[..8].try_into().unwrap()),
+                column: entry[8],
+                score: i32::from_le_bytes(entry[9..].try_into().unwrap()),
+            });
+        }
+        if let Some(entry) = entries.iter().find(|entry| entry.column as usize >= WIDTH) {
+            return Err(Error::InvalidColumn(entry.column));
+        }
+        if entries.windows(2).any(|pair| pair[0].key >= pair[1].key) {
+            return Err(Error::UnsortedEntries);
+        }
+        Ok(Self {
+            width,
+            height,
+            winning_tokens,
+            plies,
+            depth,
+            entries,
+        })
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<(), Error> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[
+            VERSION,
+            self.width,
+            self.height,
+            self.winning_tokens,
+            self.plies,
+            self.depth,
+        ])?;
+        writer.write_all(&(self.entries.len() as u32).to_le_bytes())?;
+        for entry in &self.entries {
+            writer.write_all(&entry.key.to_le_bytes())?;
+            writer.write_all(&[entry.column])?;
+            writer.write_all(&entry.score.to_le_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Not an opening book")]
+    NotABook,
+
+    #[error("Unsupported opening book version: {}", .0)]
+    UnsupportedVersion(u8),
+
+    #[error("Opening book entries are not sorted")]
+    UnsortedEntries,
+
+    #[error("Opening book is for a {}x{} board", .width, .height)]
+    WrongBoard { width: u8, height: u8 },
+
+    #[error("Invalid column in the opening book: {}", .0)]
+    InvalidColumn(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_book() -> Book {
+        Book::build::<5, 4>(&mut Engine::new(1 << 16), 3, 4, 2, |_| ())
+    }
+
+    #[test]
+    fn build() {
+        let book = test_book();
+        // 1 + 3 + 13 + 49 positions, up to mirror images.
+        assert_eq!(66, book.len());
+
+        let mut engine = Engine::new(1 << 16);
+        let mut board = Connect4::<5, 4>::new(3);
+        board.play_column(Symbol::Red, 1);
+        let expected = board.search(&mut engine, Symbol::Yellow, 2).unwrap();
+        assert_eq!(
+            Some(BookMove {
+                column: expected.column,
+                score: expected.score
+            }),
+            book.get(&board, Symbol::Yellow)
+        );
+    }
+
+    #[test]
+    fn mirror() {
+        let book = test_book();
+        let mut board = Connect4::<5, 4>::new(3);
+        let mut mirror = Connect4::<5, 4>::new(3);
+        for (column, symbol) in [(0, Symbol::Red), (1, Symbol::Yellow), (1, Symbol::Red)] {
+            board.play_column(symbol, column);
+            mirror.play_column(symbol, 4 - column);
+        }
+        let book_move = book.get(&board, Symbol::Yellow).unwrap();
+        let mirror_move = book.get(&mirror, Symbol::Yellow).unwrap();
+        assert_eq!(4 - book_move.column, mirror_move.column);
+        assert_eq!(book_move.score, mirror_move.score);
+
+        board.play_column(Symbol::Yellow, 2);
+        assert_eq!(None, book.get(&board, Symbol::Red));
+        assert_eq!(None, book.get(&Connect4::<5, 4>::new(4), Symbol::Red));
+    }
+
+    #[test]
+    fn read_write() {
+        let book = test_book();
+        let mut bytes = vec![];
+        book.write(&mut bytes).unwrap();
+        assert_eq!(14 + 13 * book.len(), bytes.len());
+        assert_eq!(book, Book::read::<5, 4>(bytes.as_slice()).unwrap());
+
+        assert!(matches!(
+            Book::read::<5, 4>(&bytes[..bytes.len() - 1]),
+            Err(Error::Io(_))
+        ));
+        assert!(matches!(
+            Book::read::<7, 6>(bytes.as_slice()),
+            Err(Error::WrongBoard {
+                width: 5,
+                height: 4
+            })
+        ));
+        let column = bytes[14 + 8];
+        bytes[14 + 8] = 5;
+        assert!(matches!(
+            Book::read::<5, 4>(bytes.as_slice()),
+            Err(Error::InvalidColumn(5))
+        ));
+        bytes[14 + 8] = column;
+        bytes[4] = 2;
+        assert!(matches!(
+            Book::read::<5, 4>(bytes.as_slice()),
+            Err(Error::UnsupportedVersion(2))
+        ));
+        bytes[0] = b'X';
+        assert!(matches!(
+            Book::read::<5, 4>(bytes.as_slice()),
+            Err(Error::NotABook)
+        ));
+    }
+}
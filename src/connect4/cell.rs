@@ -7,6 +7,16 @@ pub struct Connect4Cell {
     pub symbol: Symbol,
 }
 
+impl Connect4Cell {
+    pub fn as_xo(&self) -> &char {
+        match self.symbol {
+            Symbol::Empty => &' ',
+            Symbol::Red => &'X',
+            Symbol::Yellow => &'O',
+        }
+    }
+}
+
 impl AsChar for Connect4Cell {
     fn as_char(&self) -> char {
         self.symbol.as_char()
@@ -0,0 +1,164 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::shared::rng::Rng;
+
+use super::book::Book;
+use super::eval::EvalParams;
+use super::eval::Evaluator;
+use super::ordering::MoveOrdering;
+use super::personality::Personality;
+use super::stats::SearchStats;
+use super::transposition::TranspositionTable;
+
+/// Memory used by the transposition table of the default engine.
+const DEFAULT_TABLE_MEMORY: usize = 16 << 20;
+
+/// Number of nodes searched between two checks of the clock.
+const NODES_PER_CLOCK_CHECK: u64 = 1024;
+
+/// State of the Connect4 AI, kept from one move to the next.
+pub struct Engine {
+    /// Positions already searched, shared by the search threads.
+    pub table: Arc<TranspositionTable>,
+
+    /// Number of threads searching each position, the calling thread included.
+    ///
+    /// With more than one thread, the moves found may vary from one search to the next.
+    pub threads: usize,
+
+    /// Whether to search on a `BitBoard` copy of the board when it fits.
+    pub bitboard: bool,
+
+    /// Best moves of the first positions of a game.
+    pub book: Option<Book>,
+
+    /// Weights of the evaluation of the positions searched.
+    pub params: EvalParams,
+
+    /// Evaluation of the positions searched instead of the lines scored with `params`, if
+    /// any.
+    pub evaluator: Option<Arc<dyn Evaluator>>,
+
+    /// Order in which columns are searched.
+    pub ordering: MoveOrdering,
+
+    /// How the move played is chosen among the columns searched.
+    pub personality: Personality,
+
+    /// Random generator of the choices of `personality`.
+    pub rng: Rng,
+
+    /// Statistics of the searches of the last move.
+    pub stats: SearchStats,
+
+    /// Depth of the current search, to tell how far from the board a position is.
+    pub(super) search_depth: i32,
+
+    /// Time at which the current search must stop.
+    pub(super) deadline: Option<Instant>,
+
+    /// Whether the current search ran out of time.
+    pub(super) stopped: bool,
+
+    /// Principal variations of the positions of the current search, by depth left to search.
+    pv: Vec<Vec<i32>>,
+
+    /// For the engines of helper threads, set when the main search completed.
+    abort: Option<Arc<AtomicBool>>,
+}
+
+impl Engine {
+    /// Creates an engine whose transposition table uses at most `table_memory` bytes.
+    pub fn new(table_memory: usize) -> Self {
+        Self {
+            table: Arc::new(TranspositionTable::with_memory(table_memory)),
+            threads: 1,
+            bitboard: true,
+            params: Default::default(),
+            evaluator: None,
+            book: None,
+            ordering: MoveOrdering::default(),
+            personality: Personality::Best,
+            rng: Rng::new(0),
+            stats: SearchStats::default(),
+            search_depth: 0,
+            deadline: None,
+            stopped: false,
+            pv: vec![],
+            abort: None,
+        }
+    }
+
+    /// Engine of a helper thread, sharing the transposition table of this engine and
+    /// stopping when `abort` is set.
+    pub(super) fn helper(&self, abort: Arc<AtomicBool>) -> Self {
+        Self {
+            table: self.table.clone(),
+            threads: 1,
+            bitboard: self.bitboard,
+            params: self.params,
+            evaluator: self.evaluator.clone(),
+            book: None,
+            ordering: MoveOrdering::new(self.ordering.heuristics.clone()),
+            personality: Personality::Best,
+            rng: Rng::new(0),
+            stats: SearchStats::default(),
+            search_depth: 0,
+            deadline: self.deadline,
+            stopped: false,
+            pv: vec![],
+            abort: Some(abort),
+        }
+    }
+
+    /// Counts a new node, and tells whether the current search must stop, checking the
+    /// clock every few nodes.
+    pub(super) fn next_node(&mut self) -> bool {
+        self.stats.nodes += 1;
+        if !self.stopped && self.stats.nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) {
+            self.stopped = self
+                .abort
+                .as_ref()
+                .is_some_and(|abort| abort.load(Ordering::Relaxed))
+                || self
+                    .deadline
+                    .is_some_and(|deadline| Instant::now() >= deadline);
+        }
+        self.stopped
+    }
+
+    /// Principal variation of the last position searched with `depth` moves left.
+    pub(super) fn pv(&self, depth: i32) -> &[i32] {
+        &self.pv[depth as usize]
+    }
+
+    /// Forgets the principal variation of the position searched with `depth` moves left.
+    pub(super) fn clear_pv(&mut self, depth: i32) {
+        let depth = depth as usize;
+        if self.pv.len() <= depth {
+            self.pv.resize(depth + 1, vec![]);
+        }
+        self.pv[depth].clear();
+    }
+
+    /// Makes `column`, followed by the principal variation one move deeper unless `last`,
+    /// the principal variation with `depth` moves left.
+    pub(super) fn update_pv(&mut self, depth: i32, column: i32, last: bool) {
+        let (deeper, pv) = self.pv.split_at_mut(depth as usize);
+        let pv = &mut pv[0];
+        pv.clear();
+        pv.push(column);
+        if let Some(deeper) = deeper.last().filter(|_| !last) {
+            pv.extend_from_slice(deeper);
+        }
+    }
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_MEMORY)
+    }
+}
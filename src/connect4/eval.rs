@@ -0,0 +1,147 @@
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+use super::position::Position;
+use super::symbol::Symbol;
+
+/// Weights of the evaluation of a Connect4 board.
+///
+/// A line of `winning_tokens` cells with tokens of a single player scores
+/// `token_weight^tokens * empty_weight^empty cells` for that player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EvalParams {
+    pub token_weight: i32,
+    pub empty_weight: i32,
+}
+
+impl EvalParams {
+    /// Score of a line of `winning_tokens` cells with `tokens` tokens of a single player.
+    pub fn line_score(&self, winning_tokens: i32, tokens: i32) -> i64 {
+        (self.token_weight as i64)
+            .saturating_pow(tokens as u32)
+            .saturating_mul(
+                (self.empty_weight as i64).saturating_pow((winning_tokens - tokens) as u32),
+            )
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the weights, one `name = value` line each.
+    pub fn read(reader: impl BufRead) -> Result<Self, Error> {
+        let (mut token_weight, mut empty_weight) = (None, None);
+        for line in reader.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (name, value) = line
+                .split_once('=')
+                .ok_or_else(|| Error::InvalidLine(line.to_owned()))?;
+            let value = value
+                .trim()
+                .parse::<i32>()
+                .ok()
+                .filter(|value| *value > 0)
+                .ok_or_else(|| Error::InvalidLine(line.to_owned()))?;
+            match name.trim() {
+                "token_weight" => token_weight = Some(value),
+                "empty_weight" => empty_weight = Some(value),
+                _ => return Err(Error::InvalidLine(line.to_owned())),
+            }
+        }
+        Ok(Self {
+            token_weight: token_weight.ok_or(Error::MissingWeight("token_weight"))?,
+            empty_weight: empty_weight.ok_or(Error::MissingWeight("empty_weight"))?,
+        })
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<(), Error> {
+        writeln!(writer, "token_weight = {}", self.token_weight)?;
+        writeln!(writer, "empty_weight = {}", self.empty_weight)?;
+        Ok(())
+    }
+}
+
+impl Default for EvalParams {
+    fn default() -> Self {
+        Self {
+            token_weight: 100,
+            empty_weight: 10,
+        }
+    }
+}
+
+/// Evaluation of the positions searched by an `Engine`, instead of the lines scored with its
+/// `EvalParams`.
+///
+/// The search adds up the changes of the evaluation from one position to the next, so the
+/// score of a position for a player must be the opposite of its score for the other one.
+pub trait Evaluator: Send + Sync {
+    /// Score of `position` for `symbol`.
+    fn evaluate(&self, position: &dyn Position, symbol: Symbol) -> i32;
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid evaluation parameter: {}", .0)]
+    InvalidLine(String),
+
+    #[error("Missing evaluation parameter: {}", .0)]
+    MissingWeight(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_score() {
+        let params = EvalParams::default();
+        assert_eq!(10_000, params.line_score(4, 0));
+        assert_eq!(1_000_000, params.line_score(4, 2));
+        assert_eq!(100_000_000, params.line_score(4, 4));
+        assert_eq!(i64::MAX, params.line_score(20, 20));
+    }
+
+    #[test]
+    fn read_write() {
+        let params = EvalParams {
+            token_weight: 37,
+            empty_weight: 4,
+        };
+        let mut bytes = vec![];
+        params.write(&mut bytes).unwrap();
+        assert_eq!(
+            "token_weight = 37\nempty_weight = 4\n",
+            String::from_utf8_lossy(&bytes)
+        );
+        assert_eq!(params, EvalParams::read(bytes.as_slice()).unwrap());
+
+        assert!(matches!(
+            EvalParams::read("token_weight = 37\n".as_bytes()),
+            Err(Error::MissingWeight("empty_weight"))
+        ));
+        assert!(matches!(
+            EvalParams::read("token_weight = 0\nempty_weight = 4".as_bytes()),
+            Err(Error::InvalidLine(_))
+        ));
+        assert!(matches!(
+            EvalParams::read("center = 2".as_bytes()),
+            Err(Error::InvalidLine(_))
+        ));
+    }
+}
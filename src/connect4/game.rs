@@ -3,29 +3,42 @@ use lazy_static::lazy_static;
 use crate::shared::board::board_get_set::BoardGet;
 use crate::shared::board::board_get_set::BoardSet;
 use crate::shared::board::board_lines::BoardLines;
+use crate::shared::board::board_lines::LineIterator;
 use crate::shared::board::board_size::BoardSize;
 use crate::shared::coord::directions;
 use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
 
 use super::board::Connect4;
-use super::cell::Connect4Cell;
+use super::position::Position;
 use super::symbol::Symbol;
+use super::zobrist;
 
-impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
-    pub fn play_column(&mut self, symbol: Symbol, column: i32) -> Option<PlayColumn> {
+impl<const WIDTH: usize, const HEIGHT: usize> Position for Connect4<WIDTH, HEIGHT> {
+    fn play_column(&mut self, symbol: Symbol, column: i32) -> Option<PlayColumn> {
         if let Some(dropped_pos) = self.get_dropped_pos_mut(column) {
             *dropped_pos.symbol = symbol;
             let position = dropped_pos.position;
-            let (delta_score, end_of_game) = {
-                let my_score = self.eval_position(symbol, position);
-                let other_score = self.eval_position(symbol.other(), position);
-                let delta_score = my_score.score - other_score.score;
-                let end_of_game = my_score.end_of_game || other_score.end_of_game;
-                (delta_score, end_of_game)
+            self.hash ^= zobrist::key(position, symbol);
+            self.mirror_hash ^= zobrist::mirror_key(WIDTH as i32, position, symbol);
+            let (delta_score, full_line) = self.tally.play(position, symbol);
+            let outcome = match full_line {
+                Some(line) => GameOutcome::Win {
+                    winner: symbol,
+                    line,
+                },
+                // The board is full once the top row is.
+                None if position.y == 0
+                    && (0..WIDTH as i32)
+                        .all(|x| self.get(&Point::new(x, 0)).symbol != Symbol::Empty) =>
+                {
+                    GameOutcome::Draw
+                }
+                None => GameOutcome::Ongoing,
             };
             Some(PlayColumn {
-                delta_score,
-                end_of_game,
+                delta_score: delta_score.clamp(i32::MIN as i64, i32::MAX as i64) as i32,
+                outcome,
                 position,
             })
         } else {
@@ -33,6 +46,30 @@ impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
         }
     }
 
+    fn undo_column(&mut self, play_column: &PlayColumn) {
+        let cell = self.get_mut(&play_column.position);
+        let symbol = cell.symbol;
+        debug_assert!(symbol != Symbol::Empty);
+        cell.symbol = Symbol::Empty;
+        self.hash ^= zobrist::key(play_column.position, symbol);
+        self.mirror_hash ^= zobrist::mirror_key(WIDTH as i32, play_column.position, symbol);
+        self.tally.undo(play_column.position, symbol);
+    }
+
+    fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    fn mirror_hash(&self) -> u64 {
+        self.mirror_hash
+    }
+
+    fn is_dead(&self) -> bool {
+        self.tally.is_dead()
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
     #[cfg(test)]
     pub fn get_dropped_pos(&self, column: i32) -> Option<DroppedPos> {
         use crate::shared::board::board_iterate::ro::BoardIterate;
@@ -56,7 +93,9 @@ impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
             })
     }
 
-    fn eval_position(&self, symbol: Symbol, position: Point) -> Score {
+    /// Score of the lines going through `position` for `symbol`, scanning them.
+    #[cfg(test)]
+    pub(super) fn eval_position(&self, symbol: Symbol, position: Point) -> Score {
         let mut end_of_game = false;
         let score = self
             .get_intersecting_lines(position)
@@ -83,45 +122,57 @@ impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
         Score { score, end_of_game }
     }
 
+    #[cfg(test)]
     fn get_intersecting_lines<'a>(
         &'a self,
         position: Point,
     ) -> impl Iterator<Item = impl Iterator<Item = Point>> + 'a {
-        lazy_static! {
-            // TODO; Create a proc macro where '+' operator is re-written as call to 'a.plus(b) that can be const'
-            static ref DIRECTIONS: [Point; 4] = [
-                directions::UP,
-                directions::RIGHT,
-                directions::UP + directions::RIGHT,
-                directions::UP + directions::LEFT,
-            ];
-        }
-        DIRECTIONS
-            .iter()
-            .map(move |direction| self.lines(position, *direction, self.winning_tokens))
-            .flatten()
+        intersecting_lines(self, position, self.winning_tokens)
+    }
+}
+
+/// All the lines of `length` cells of `board` going through `position`.
+pub(super) fn intersecting_lines(
+    board: &impl BoardSize,
+    position: Point,
+    length: i32,
+) -> impl Iterator<Item = LineIterator> + '_ {
+    lazy_static! {
+        // TODO; Create a proc macro where '+' operator is re-written as call to 'a.plus(b) that can be const'
+        static ref DIRECTIONS: [Point; 4] = [
+            directions::UP,
+            directions::RIGHT,
+            directions::UP + directions::RIGHT,
+            directions::UP + directions::LEFT,
+        ];
     }
+    DIRECTIONS
+        .iter()
+        .map(move |direction| board.lines(position, *direction, length))
+        .flatten()
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct PlayColumn {
     pub delta_score: i32,
-    pub end_of_game: bool,
+
+    /// Whether the token won the game, filled the board, or neither.
+    pub outcome: GameOutcome<Symbol>,
+
     pub position: Point,
 }
 
 impl PlayColumn {
-    pub fn undo<B: BoardSet<Value = Connect4Cell>>(&self, board: &mut B) {
-        let cell = board.get_mut(&self.position);
-        debug_assert!(cell.symbol != Symbol::Empty);
-        cell.symbol = Symbol::Empty;
+    pub fn undo(&self, board: &mut impl Position) {
+        board.undo_column(self)
     }
 }
 
+#[cfg(test)]
 #[derive(Debug, PartialEq, Eq)]
-struct Score {
-    score: i32,
-    end_of_game: bool,
+pub(super) struct Score {
+    pub(super) score: i32,
+    pub(super) end_of_game: bool,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -141,6 +192,8 @@ mod tests {
     use crate::shared::board::board_size::BoardSize;
 
     use super::*;
+    use crate::connect4::bitboard::BitBoard;
+    use crate::connect4::cell::Connect4Cell;
 
     #[test]
     fn get_dropped_pos() {
@@ -213,6 +266,92 @@ mod tests {
         );
     }
 
+    #[test]
+    fn hash() {
+        let mut board = Connect4::<5, 4>::new(3);
+        assert_eq!(0, board.hash());
+
+        let play_red = board.play_column(Symbol::Red, 1).unwrap();
+        let play_yellow = board.play_column(Symbol::Yellow, 2).unwrap();
+        let hash = board.hash();
+        assert_ne!(0, hash);
+
+        play_yellow.undo(&mut board);
+        play_red.undo(&mut board);
+        assert_eq!(0, board.hash());
+
+        board.play_column(Symbol::Yellow, 2);
+        board.play_column(Symbol::Red, 1);
+        assert_eq!(hash, board.hash());
+
+        board.play_column(Symbol::Red, 1);
+        assert_ne!(hash, board.hash());
+    }
+
+    #[test]
+    fn draw() {
+        let mut board = Connect4::<3, 2>::new(3);
+        let mut bitboard = BitBoard::<3, 2>::new(3, Symbol::Red);
+        let mut symbol = Symbol::Red;
+        for column in [0, 1, 2, 0, 1] {
+            let play_column = board.play_column(symbol, column).unwrap();
+            assert_eq!(GameOutcome::Ongoing, play_column.outcome);
+            bitboard.play_column(symbol, column);
+            symbol = symbol.other();
+        }
+        let play_column = board.play_column(Symbol::Yellow, 2).unwrap();
+        assert_eq!(GameOutcome::Draw, play_column.outcome);
+        assert_eq!(Some(play_column), bitboard.play_column(Symbol::Yellow, 2));
+    }
+
+    #[test]
+    fn dead() {
+        let mut board = Connect4::<4, 2>::new(3);
+        let mut bitboard = BitBoard::<4, 2>::new(3, Symbol::Red);
+        for (column, symbol) in [
+            (0, Symbol::Red),
+            (1, Symbol::Yellow),
+            (2, Symbol::Red),
+            (3, Symbol::Yellow),
+            (1, Symbol::Red),
+        ] {
+            board.play_column(symbol, column);
+            bitboard.play_column(symbol, column);
+            assert!(!board.is_dead());
+            assert!(!bitboard.is_dead());
+        }
+        // Both lines of the top row get tokens of both players, with 2 empty cells left.
+        let play_column = board.play_column(Symbol::Yellow, 2).unwrap();
+        bitboard.play_column(Symbol::Yellow, 2);
+        assert_eq!(GameOutcome::Ongoing, play_column.outcome);
+        assert!(board.is_dead());
+        assert!(bitboard.is_dead());
+
+        play_column.undo(&mut board);
+        assert!(!board.is_dead());
+    }
+
+    #[test]
+    fn mirror_hash() {
+        let mut board = Connect4::<5, 4>::new(3);
+        let mut mirror = Connect4::<5, 4>::new(3);
+        assert!(board.is_symmetric());
+        for (column, symbol) in [(0, Symbol::Red), (1, Symbol::Yellow), (1, Symbol::Red)] {
+            board.play_column(symbol, column);
+            mirror.play_column(symbol, 4 - column);
+        }
+        assert_eq!(board.hash(), mirror.mirror_hash());
+        assert_eq!(board.mirror_hash(), mirror.hash());
+        assert!(!board.is_symmetric());
+
+        for (column, symbol) in [(4, Symbol::Red), (3, Symbol::Yellow), (3, Symbol::Red)] {
+            board.play_column(symbol, column);
+        }
+        assert!(board.is_symmetric());
+        board.play_column(Symbol::Yellow, 2);
+        assert!(board.is_symmetric());
+    }
+
     #[test]
     fn get_intersecting_lines_0_0() {
         let mut board = Connect4::<5, 4>::new(3);
@@ -0,0 +1,361 @@
+use std::fmt::Display;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::shared::board::board_all_points::BoardAllPoints;
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::rng::splitmix64;
+use crate::shared::rng::Rng;
+
+use super::board::Connect4;
+use super::engine::Engine;
+use super::personality::Personality;
+use super::solver::Solver;
+use super::strategy::Move;
+use super::symbol::Symbol;
+use super::tuning::play_players;
+use super::tuning::Player;
+
+/// Memory of the transposition table of the engine of each player of a calibration match.
+const ENGINE_MEMORY: usize = 1 << 20;
+
+/// Memory of the solver of each `Perfect` player of a calibration match.
+const SOLVER_MEMORY: usize = 8 << 20;
+
+/// Number of tokens on the board from which `Perfect` plays the moves of the solver, which
+/// is too slow on the first moves of a 7x6 board.
+const PERFECT_TOKENS: usize = 10;
+
+/// Named strength of the computer, from the weakest to the strongest.
+///
+/// Each level beats the previous one, which `calibrate` measures on a given board.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Searches 1 move ahead, and plays a random worse move half of the time.
+    Beginner,
+
+    /// Searches 2 moves ahead, and plays a random worse move a quarter of the time.
+    Novice,
+
+    /// Searches 4 moves ahead, and plays a random worse move a tenth of the time.
+    #[default]
+    Intermediate,
+
+    /// Searches 6 moves ahead.
+    Advanced,
+
+    /// Searches 10 moves ahead.
+    Expert,
+
+    /// Searches 12 moves ahead on the first moves, then plays the moves of the solver.
+    Perfect,
+}
+
+/// Result of a match of `calibrate`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub level: Level,
+
+    /// Share of the points won by `level` against the previous level.
+    pub score: f64,
+}
+
+impl Level {
+    pub const ALL: [Level; 6] = [
+        Level::Beginner,
+        Level::Novice,
+        Level::Intermediate,
+        Level::Advanced,
+        Level::Expert,
+        Level::Perfect,
+    ];
+
+    /// How many moves ahead the computer searches.
+    pub fn depth(&self) -> i32 {
+        match self {
+            Level::Beginner => 1,
+            Level::Novice => 2,
+            Level::Intermediate => 4,
+            Level::Advanced => 6,
+            Level::Expert => 10,
+            Level::Perfect => 12,
+        }
+    }
+
+    /// How the computer chooses its move among the columns it searched.
+    pub fn personality(&self) -> Personality {
+        match self {
+            Level::Beginner => Personality::Blunder { rate: 0.5 },
+            Level::Novice => Personality::Blunder { rate: 0.25 },
+            Level::Intermediate => Personality::Blunder { rate: 0.1 },
+            Level::Advanced | Level::Expert | Level::Perfect => Personality::Best,
+        }
+    }
+
+    /// Number of tokens on the board from which the computer plays the moves of the solver,
+    /// if it ever does.
+    pub fn solved_from(&self) -> Option<usize> {
+        match self {
+            Level::Perfect => Some(PERFECT_TOKENS),
+            _ => None,
+        }
+    }
+
+    /// Engine playing at this level, its random choices coming from `seed`.
+    pub fn engine(&self, table_memory: usize, seed: u64) -> Engine {
+        let mut engine = Engine::new(table_memory);
+        engine.personality = self.personality();
+        engine.rng = Rng::new(seed);
+        engine
+    }
+
+    /// Plays the move of `symbol` at this level, with `engine` set up by `Level::engine`.
+    ///
+    /// The solver falls back to the search of `engine` when the board doesn't fit in a
+    /// bitboard.
+    pub fn next_move<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        board: &mut Connect4<WIDTH, HEIGHT>,
+        engine: &mut Engine,
+        solver: &mut Solver,
+        symbol: Symbol,
+    ) -> Option<Move> {
+        let tokens = board
+            .all_points()
+            .filter(|point| board.get(point).symbol != Symbol::Empty)
+            .count();
+        if self
+            .solved_from()
+            .is_some_and(|solved_from| tokens >= solved_from)
+        {
+            if let Some(solved_move) = board.next_move_solved(solver, symbol) {
+                return Some(solved_move);
+            }
+        }
+        board.next_move_with(engine, symbol, self.depth())
+    }
+
+    /// Player of a calibration match, its random choices coming from `seed`.
+    fn player<'p, const WIDTH: usize, const HEIGHT: usize>(
+        self,
+        seed: u64,
+    ) -> Player<'p, WIDTH, HEIGHT> {
+        let mut engine = self.engine(ENGINE_MEMORY, seed);
+        let mut solver = Solver::new(if self.solved_from().is_some() {
+            SOLVER_MEMORY
+        } else {
+            0
+        });
+        Box::new(move |board, symbol| self.next_move(board, &mut engine, &mut solver, symbol))
+    }
+}
+
+/// Plays each level against the previous one, and returns the share of the points it won.
+///
+/// The random choices of the players only depend on the number of the game, so that the
+/// same rules always give the same table. `progress` is called after each match.
+pub fn calibrate<const WIDTH: usize, const HEIGHT: usize>(
+    winning_tokens: i32,
+    mut progress: impl FnMut(&Calibration),
+) -> Vec<Calibration> {
+    Level::ALL
+        .windows(2)
+        .map(|levels| {
+            let [previous, level] = [levels[0], levels[1]];
+            let score = play_players::<WIDTH, HEIGHT>(winning_tokens, |game| {
+                let seed = splitmix64(game);
+                [level.player(seed), previous.player(!seed)]
+            });
+            let calibration = Calibration { level, score };
+            progress(&calibration);
+            calibration
+        })
+        .collect()
+}
+
+pub fn load_calibration(path: impl AsRef<Path>) -> Result<Vec<Calibration>, Error> {
+    read_calibration(std::io::BufReader::new(std::fs::File::open(path)?))
+}
+
+pub fn save_calibration(path: impl AsRef<Path>, calibration: &[Calibration]) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_calibration(&mut writer, calibration)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the calibration, one `level = score` line per level.
+pub fn read_calibration(reader: impl BufRead) -> Result<Vec<Calibration>, Error> {
+    let mut calibration = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let invalid = || Error::InvalidLine(line.to_owned());
+        let (level, score) = line.split_once('=').ok_or_else(invalid)?;
+        calibration.push(Calibration {
+            level: level.trim().parse()?,
+            score: score
+                .trim()
+                .parse()
+                .ok()
+                .filter(|score| (0. ..=1.).contains(score))
+                .ok_or_else(invalid)?,
+        });
+    }
+    Ok(calibration)
+}
+
+pub fn write_calibration(mut writer: impl Write, calibration: &[Calibration]) -> Result<(), Error> {
+    for Calibration { level, score } in calibration {
+        writeln!(writer, "{level} = {score:.3}")?;
+    }
+    Ok(())
+}
+
+impl Display for Level {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Level::Beginner => "beginner",
+            Level::Novice => "novice",
+            Level::Intermediate => "intermediate",
+            Level::Advanced => "advanced",
+            Level::Expert => "expert",
+            Level::Perfect => "perfect",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Level {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .into_iter()
+            .find(|level| level.to_string() == text)
+            .ok_or_else(|| Error::InvalidLevel(text.to_owned()))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid level: {}, expected beginner, novice, intermediate, advanced, expert or perfect", .0)]
+    InvalidLevel(String),
+
+    #[error("Invalid calibration: {}", .0)]
+    InvalidLine(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connect4::position::Position;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        for level in Level::ALL {
+            assert_eq!(level, level.to_string().parse::<Level>().unwrap());
+        }
+        assert!(matches!(
+            "master".parse::<Level>(),
+            Err(Error::InvalidLevel(_))
+        ));
+        assert!(Level::ALL.windows(2).all(|levels| levels[0] < levels[1]));
+    }
+
+    #[test]
+    fn next_move() {
+        let mut board = Connect4::<5, 4>::new(3);
+        let mut solver = Solver::new(1 << 16);
+        let mut engine = Level::Perfect.engine(1 << 16, 0);
+        for (column, symbol) in [(0, Symbol::Red), (1, Symbol::Yellow)] {
+            board.play_column(symbol, column);
+        }
+        // Below `PERFECT_TOKENS` tokens, the move is searched.
+        let next_move = Level::Perfect
+            .next_move(&mut board, &mut engine, &mut solver, Symbol::Red)
+            .unwrap();
+        assert_eq!(0, solver.nodes());
+        assert!(next_move.pv.len() > 1);
+
+        let mut symbol = Symbol::Red;
+        for column in [2, 3, 4, 0, 1, 2, 3, 4] {
+            assert!(!board.play_column(symbol, column).unwrap().outcome.is_over());
+            symbol = symbol.other();
+        }
+        let next_move = Level::Perfect
+            .next_move(&mut board, &mut engine, &mut solver, Symbol::Red)
+            .unwrap();
+        assert_eq!((2, vec![2]), (next_move.column, next_move.pv));
+        assert!(next_move.outcome.is_over());
+    }
+
+    #[test]
+    fn calibrate() {
+        let mut matches = 0;
+        let calibration = super::calibrate::<4, 4>(3, |_| matches += 1);
+        assert_eq!(5, matches);
+        assert_eq!(
+            Level::ALL[1..].to_vec(),
+            calibration
+                .iter()
+                .map(|calibration| calibration.level)
+                .collect::<Vec<_>>()
+        );
+        assert!(calibration
+            .iter()
+            .all(|calibration| (0. ..=1.).contains(&calibration.score)));
+        assert_eq!(calibration, super::calibrate::<4, 4>(3, |_| ()));
+    }
+
+    #[test]
+    fn each_level_beats_the_previous_one() {
+        // Smaller boards are solved by the deeper searches, which then always draw.
+        for calibration in super::calibrate::<5, 5>(4, |_| ()) {
+            assert!(calibration.score > 0.5, "{calibration:?}");
+        }
+    }
+
+    #[test]
+    fn read_write() {
+        let calibration = vec![
+            Calibration {
+                level: Level::Novice,
+                score: 0.75,
+            },
+            Calibration {
+                level: Level::Perfect,
+                score: 0.5,
+            },
+        ];
+        let mut bytes = vec![];
+        write_calibration(&mut bytes, &calibration).unwrap();
+        assert_eq!(
+            "novice = 0.750\nperfect = 0.500\n",
+            String::from_utf8_lossy(&bytes)
+        );
+        assert_eq!(calibration, read_calibration(bytes.as_slice()).unwrap());
+
+        assert!(matches!(
+            read_calibration("novice 0.75".as_bytes()),
+            Err(Error::InvalidLine(_))
+        ));
+        assert!(matches!(
+            read_calibration("novice = 2".as_bytes()),
+            Err(Error::InvalidLine(_))
+        ));
+        assert!(matches!(
+            read_calibration("master = 0.5".as_bytes()),
+            Err(Error::InvalidLevel(_))
+        ));
+    }
+}
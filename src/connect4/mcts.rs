@@ -0,0 +1,359 @@
+use std::cmp::Reverse;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+use crate::shared::rng::Rng;
+
+use super::bitboard::BitBoard;
+use super::board::Connect4;
+use super::game::PlayColumn;
+use super::position::Position;
+use super::symbol::Symbol;
+
+/// Exploration constant of UCT, the theoretical `sqrt(2)`.
+const DEFAULT_EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// How long a Monte Carlo tree search runs.
+#[derive(Clone, Copy, Debug)]
+pub enum MctsBudget {
+    /// Runs the given number of playouts.
+    Playouts(u32),
+
+    /// Runs playouts until the given time elapsed, at least one.
+    Time(Duration),
+}
+
+/// Monte Carlo tree search player, picking the moves to explore with UCT.
+///
+/// Unlike the negamax search, it doesn't use the scores of the board: each playout plays
+/// random moves until the end of the game, and the moves are ranked by how often they win.
+pub struct Mcts {
+    /// Weight of the exploration of the moves visited the least, against the exploitation
+    /// of the moves winning the most.
+    pub exploration: f64,
+
+    /// Random generator of the playouts.
+    rng: Rng,
+
+    /// Number of playouts run.
+    playouts: u64,
+}
+
+/// Statistics of a move of the player to move.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RootMove {
+    pub column: i32,
+
+    /// Number of playouts that started with the move.
+    pub visits: u32,
+
+    /// Share of these playouts won by the player to move, draws counting as half a win.
+    pub win_rate: f64,
+}
+
+/// Move played by a Monte Carlo tree search.
+#[derive(Debug)]
+pub struct MctsMove {
+    pub column: i32,
+
+    /// Whether the move won the game, filled the board, or neither.
+    pub outcome: GameOutcome<Symbol>,
+
+    /// Statistics of all the moves searched, by column.
+    pub root_moves: Vec<RootMove>,
+}
+
+/// Node of the search tree, the position reached by playing `column`.
+struct Node {
+    column: i32,
+    visits: u32,
+
+    /// Playouts won by the player who played `column`, draws counting as half.
+    wins: f64,
+
+    children: Vec<usize>,
+
+    /// Playable columns without a child node yet.
+    untried: Vec<i32>,
+
+    /// Whether the player who played `column` won.
+    won: bool,
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// Plays the move of `symbol` visited the most by a Monte Carlo tree search.
+    pub fn next_move_mcts(
+        &mut self,
+        mcts: &mut Mcts,
+        symbol: Symbol,
+        budget: MctsBudget,
+    ) -> Option<MctsMove> {
+        let root_moves = mcts.search(self, symbol, budget);
+        let column = root_moves
+            .iter()
+            .min_by_key(|root_move| Reverse(root_move.visits))?
+            .column;
+        Some(MctsMove {
+            column,
+            outcome: self.play_column(symbol, column)?.outcome,
+            root_moves,
+        })
+    }
+}
+
+impl Mcts {
+    /// Creates a player whose playouts are generated from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            exploration: DEFAULT_EXPLORATION,
+            rng: Rng::new(seed),
+            playouts: 0,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn playouts(&self) -> u64 {
+        self.playouts
+    }
+
+    /// Searches the moves of `symbol` on `board`, on a `BitBoard` copy of the board when it
+    /// fits.
+    ///
+    /// Returns the statistics of the moves, by column.
+    pub fn search<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        board: &mut Connect4<WIDTH, HEIGHT>,
+        symbol: Symbol,
+        budget: MctsBudget,
+    ) -> Vec<RootMove> {
+        if let Some(mut bitboard) = BitBoard::from_board(board, symbol) {
+            self.search_impl(&mut bitboard, symbol, budget)
+        } else {
+            self.search_impl(board, symbol, budget)
+        }
+    }
+
+    fn search_impl(
+        &mut self,
+        position: &mut impl Position,
+        symbol: Symbol,
+        budget: MctsBudget,
+    ) -> Vec<RootMove> {
+        let start = Instant::now();
+        let mut nodes = vec![Node::new(-1, false, playable_columns(position))];
+        let mut path: Vec<(usize, PlayColumn)> = vec![];
+        let mut playouts = 0;
+        while match budget {
+            MctsBudget::Playouts(max_playouts) => playouts < max_playouts,
+            MctsBudget::Time(budget) => playouts == 0 || start.elapsed() < budget,
+        } {
+            // Selection of the most promising node whose moves were all tried.
+            let mut node = 0;
+            let mut to_move = symbol;
+            while nodes[node].untried.is_empty() && !nodes[node].children.is_empty() {
+                node = self.select(&nodes, node);
+                let play_column = position.play_column(to_move, nodes[node].column).unwrap();
+                path.push((node, play_column));
+                to_move = to_move.other();
+            }
+
+            // Expansion of one of its moves not tried yet.
+            if !nodes[node].untried.is_empty() {
+                let index = self.rng.below(nodes[node].untried.len());
+                let column = nodes[node].untried.swap_remove(index);
+                let play_column = position.play_column(to_move, column).unwrap();
+                let untried = if play_column.outcome.is_over() {
+                    vec![]
+                } else {
+                    playable_columns(position)
+                };
+                let won = play_column.outcome.winner().is_some();
+                nodes.push(Node::new(column, won, untried));
+                let child = nodes.len() - 1;
+                nodes[node].children.push(child);
+                path.push((child, play_column));
+                node = child;
+                to_move = to_move.other();
+            }
+
+            let winner = if nodes[node].won {
+                Some(to_move.other())
+            } else {
+                self.playout(position, to_move)
+            };
+
+            nodes[0].visits += 1;
+            let mut mover = symbol;
+            for (node, _) in &path {
+                let node = &mut nodes[*node];
+                node.visits += 1;
+                node.wins += match winner {
+                    Some(winner) if winner == mover => 1.,
+                    Some(_) => 0.,
+                    None => 0.5,
+                };
+                mover = mover.other();
+            }
+            for (_, play_column) in path.drain(..).rev() {
+                play_column.undo(position);
+            }
+            playouts += 1;
+        }
+        self.playouts += playouts as u64;
+
+        let mut root_moves: Vec<RootMove> = nodes[0]
+            .children
+            .iter()
+            .map(|child| {
+                let child = &nodes[*child];
+                RootMove {
+                    column: child.column,
+                    visits: child.visits,
+                    win_rate: child.wins / child.visits as f64,
+                }
+            })
+            .collect();
+        root_moves.sort_by_key(|root_move| root_move.column);
+        root_moves
+    }
+
+    /// The child of `parent` with the best upper confidence bound.
+    fn select(&self, nodes: &[Node], parent: usize) -> usize {
+        let log_visits = (nodes[parent].visits as f64).ln();
+        let uct = |child: usize| {
+            let child = &nodes[child];
+            let visits = child.visits as f64;
+            child.wins / visits + self.exploration * (log_visits / visits).sqrt()
+        };
+        nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .max_by(|a, b| uct(*a).total_cmp(&uct(*b)))
+            .unwrap()
+    }
+
+    /// Plays random moves from `position`, `symbol` to move, until the end of the game, and
+    /// returns the winner, if any.
+    fn playout(&mut self, position: &mut impl Position, mut symbol: Symbol) -> Option<Symbol> {
+        let mut played = vec![];
+        let mut winner = None;
+        while let Some(&column) = self.rng.choose(&playable_columns(position)) {
+            let play_column = position.play_column(symbol, column).unwrap();
+            let outcome = play_column.outcome;
+            played.push(play_column);
+            if outcome.is_over() {
+                winner = outcome.winner();
+                break;
+            }
+            symbol = symbol.other();
+        }
+        for play_column in played.iter().rev() {
+            play_column.undo(position);
+        }
+        winner
+    }
+}
+
+impl Default for Mcts {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Node {
+    fn new(column: i32, won: bool, untried: Vec<i32>) -> Self {
+        Self {
+            column,
+            visits: 0,
+            wins: 0.,
+            children: vec![],
+            untried,
+            won,
+        }
+    }
+}
+
+fn playable_columns(position: &impl Position) -> Vec<i32> {
+    (0..position.width())
+        .filter(|column| position.get(&Point::new(*column, 0)).symbol == Symbol::Empty)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shared::board::board_get_set::BoardGet;
+
+    use super::*;
+
+    #[test]
+    fn playouts() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for _ in 0..6 {
+            board.play_column(Symbol::Red, 2);
+        }
+        let before = format!("{board}");
+        let mut mcts = Mcts::default();
+        let root_moves = mcts.search(&mut board, Symbol::Yellow, MctsBudget::Playouts(500));
+        assert_eq!(before, format!("{board}"));
+        assert_eq!(500, mcts.playouts());
+        assert_eq!(
+            vec![0, 1, 3, 4, 5, 6],
+            root_moves
+                .iter()
+                .map(|root_move| root_move.column)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            500,
+            root_moves
+                .iter()
+                .map(|root_move| root_move.visits)
+                .sum::<u32>()
+        );
+
+        let root_moves = mcts.search(&mut board, Symbol::Yellow, MctsBudget::Time(Duration::ZERO));
+        assert_eq!(501, mcts.playouts());
+        assert_eq!(1, root_moves.len());
+    }
+
+    #[test]
+    fn win_and_block() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for column in [0, 1, 2] {
+            board.play_column(Symbol::Red, column);
+        }
+        board.play_column(Symbol::Yellow, 0);
+        board.play_column(Symbol::Yellow, 1);
+
+        let mut mcts = Mcts::default();
+        let budget = MctsBudget::Playouts(2000);
+        let root_moves = mcts.search(&mut board, Symbol::Red, budget);
+        let best = root_moves.iter().max_by_key(|root_move| root_move.visits);
+        println!("{root_moves:?}");
+        assert!(matches!(best, Some(RootMove { column: 3, win_rate, .. }) if *win_rate > 0.9));
+
+        let next_move = board
+            .next_move_mcts(&mut mcts, Symbol::Yellow, budget)
+            .unwrap();
+        assert_eq!(3, next_move.column);
+        assert_eq!(GameOutcome::Ongoing, next_move.outcome);
+    }
+
+    #[test]
+    fn large_board() {
+        let mut board = Connect4::<9, 8>::new(4);
+        assert!(BitBoard::from_board(&board, Symbol::Red).is_none());
+        let mut mcts = Mcts::default();
+        let next_move = board
+            .next_move_mcts(&mut mcts, Symbol::Red, MctsBudget::Playouts(50))
+            .unwrap();
+        assert_eq!(9, next_move.root_moves.len());
+        assert_eq!(
+            Symbol::Red,
+            board.get(&Point::new(next_move.column, 7)).symbol
+        );
+    }
+}
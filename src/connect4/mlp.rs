@@ -0,0 +1,412 @@
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+
+use crate::shared::coord::point::Point;
+use crate::shared::rng::Rng;
+
+use super::eval::Evaluator;
+use super::position::Position;
+use super::selfplay;
+use super::selfplay::Record;
+use super::symbol::Symbol;
+
+/// Score of a position whose network output is 1, a sure win for Red.
+const SCORE_SCALE: f32 = 10_000.;
+
+/// Scale of the counts of windows fed to the network, so that its inputs stay around 1.
+const FEATURE_SCALE: f32 = 0.125;
+
+/// Small neural network evaluating Connect4 positions, trained on games of self-play.
+///
+/// The inputs are window-pattern features: for each player, how many windows of
+/// `winning_tokens` cells have no token of the other player and 1, 2, ... tokens of theirs,
+/// the windows one token short of a line split by the parity of the row of their empty
+/// cell. A hidden layer of `tanh` neurons feeds a single `tanh` output, the expected result
+/// of the game for Red between -1 and 1.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Mlp {
+    pub winning_tokens: i32,
+
+    /// Weights of each hidden neuron, one per input followed by the bias.
+    hidden: Vec<Vec<f32>>,
+
+    /// Weights of the output, one per hidden neuron followed by the bias.
+    output: Vec<f32>,
+}
+
+/// Features of a position and the result of its game for Red.
+#[derive(Clone, Debug)]
+pub struct Sample {
+    pub features: Vec<f32>,
+    pub target: f32,
+}
+
+impl Mlp {
+    /// Network with `hidden` neurons for boards of `winning_tokens`, whose weights are drawn
+    /// at random from `seed`.
+    pub fn new(winning_tokens: i32, hidden: usize, seed: u64) -> Self {
+        let inputs = feature_count(winning_tokens);
+        let mut rng = Rng::new(seed);
+        let mut random = |fan_in: usize| ((rng.unit() * 2. - 1.) / (fan_in as f64).sqrt()) as f32;
+        Self {
+            winning_tokens,
+            hidden: (0..hidden)
+                .map(|_| (0..=inputs).map(|_| random(inputs)).collect())
+                .collect(),
+            output: (0..=hidden).map(|_| random(hidden)).collect(),
+        }
+    }
+
+    /// Expected result of the game for Red, between -1 and 1.
+    pub fn predict(&self, features: &[f32]) -> f32 {
+        self.forward(features).1
+    }
+
+    /// Trains the network by stochastic gradient descent on the squared error, `epochs`
+    /// times over `samples` shuffled from `seed`.
+    ///
+    /// `progress` is called with the mean squared error of each epoch.
+    pub fn train(
+        &mut self,
+        samples: &[Sample],
+        epochs: usize,
+        learning_rate: f32,
+        seed: u64,
+        mut progress: impl FnMut(f32),
+    ) {
+        let mut rng = Rng::new(seed);
+        let mut order: Vec<usize> = (0..samples.len()).collect();
+        for _ in 0..epochs {
+            // Fisher-Yates shuffle.
+            for i in (1..order.len()).rev() {
+                order.swap(i, rng.below(i + 1));
+            }
+            let mut error = 0.;
+            for sample in order.iter().map(|i| &samples[*i]) {
+                error += self.step(sample, learning_rate);
+            }
+            progress(error / samples.len().max(1) as f32);
+        }
+    }
+
+    /// Moves the weights against the gradient of the squared error of `sample`, and returns
+    /// the error before the step.
+    fn step(&mut self, sample: &Sample, learning_rate: f32) -> f32 {
+        let (hidden, output) = self.forward(&sample.features);
+        let delta = (output - sample.target) * (1. - output * output);
+        let inputs = sample.features.len();
+        for (j, activation) in hidden.iter().enumerate() {
+            let hidden_delta = delta * self.output[j] * (1. - activation * activation);
+            self.output[j] -= learning_rate * delta * activation;
+            let weights = &mut self.hidden[j];
+            for (weight, feature) in weights.iter_mut().zip(&sample.features) {
+                *weight -= learning_rate * hidden_delta * feature;
+            }
+            weights[inputs] -= learning_rate * hidden_delta;
+        }
+        self.output[hidden.len()] -= learning_rate * delta;
+        (output - sample.target).powi(2)
+    }
+
+    /// Activations of the hidden layer and output of the network.
+    fn forward(&self, features: &[f32]) -> (Vec<f32>, f32) {
+        let hidden: Vec<f32> = self
+            .hidden
+            .iter()
+            .map(|weights| weighted_sum(weights, features).tanh())
+            .collect();
+        let output = weighted_sum(&self.output, &hidden).tanh();
+        (hidden, output)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::read(std::io::BufReader::new(std::fs::File::open(path)?))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+        self.write(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Reads the network: `winning_tokens = N` and `hidden = N` lines, then the weights of
+    /// each hidden neuron and of the output, one line each.
+    pub fn read(reader: impl BufRead) -> Result<Self, Error> {
+        let mut lines = reader.lines();
+        let mut next_line = || -> Result<String, Error> {
+            loop {
+                let line = lines.next().ok_or(Error::Truncated)??;
+                if !line.trim().is_empty() {
+                    return Ok(line);
+                }
+            }
+        };
+        let mut header = |name: &str| -> Result<i32, Error> {
+            let line = next_line()?;
+            line.split_once('=')
+                .filter(|(key, _)| key.trim() == name)
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .filter(|value| *value > 0)
+                .ok_or(Error::InvalidLine(line))
+        };
+        let winning_tokens = header("winning_tokens")?;
+        let hidden = header("hidden")? as usize;
+        let mut weights = |count: usize| -> Result<Vec<f32>, Error> {
+            let line = next_line()?;
+            line.split_whitespace()
+                .map(|weight| weight.parse().ok())
+                .collect::<Option<Vec<f32>>>()
+                .filter(|weights| weights.len() == count)
+                .ok_or(Error::InvalidLine(line))
+        };
+        let inputs = feature_count(winning_tokens);
+        Ok(Self {
+            winning_tokens,
+            hidden: (0..hidden)
+                .map(|_| weights(inputs + 1))
+                .collect::<Result<_, _>>()?,
+            output: weights(hidden + 1)?,
+        })
+    }
+
+    pub fn write(&self, mut writer: impl Write) -> Result<(), Error> {
+        writeln!(writer, "winning_tokens = {}", self.winning_tokens)?;
+        writeln!(writer, "hidden = {}", self.hidden.len())?;
+        for weights in self.hidden.iter().chain(std::iter::once(&self.output)) {
+            let weights: Vec<String> = weights.iter().map(f32::to_string).collect();
+            writeln!(writer, "{}", weights.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Evaluator for Mlp {
+    fn evaluate(&self, position: &dyn Position, symbol: Symbol) -> i32 {
+        let features = features(
+            position.width(),
+            position.height(),
+            self.winning_tokens,
+            |point| position.get(&point).symbol,
+        );
+        let score = (self.predict(&features) * SCORE_SCALE) as i32;
+        if symbol == Symbol::Red {
+            score
+        } else {
+            -score
+        }
+    }
+}
+
+impl Sample {
+    /// Sample of a position of a game of self-play, or `None` if the position is invalid.
+    pub fn from_record(record: &Record, winning_tokens: i32) -> Option<Self> {
+        let cells = record.cells()?;
+        let height = cells.len() as i32;
+        let width = cells.first()?.len() as i32;
+        if cells.iter().any(|row| row.len() as i32 != width) {
+            return None;
+        }
+        let features = features(width, height, winning_tokens, |point| {
+            cells[point.y as usize][point.x as usize]
+        });
+        let result = if record.to_move == Symbol::Red {
+            record.result
+        } else {
+            -record.result
+        };
+        Some(Self {
+            features,
+            target: result as f32,
+        })
+    }
+}
+
+/// Reads the samples of the positions written by `SelfPlay::export`.
+pub fn read_samples(reader: impl BufRead, winning_tokens: i32) -> Result<Vec<Sample>, Error> {
+    selfplay::read_records(reader)?
+        .iter()
+        .map(|record| {
+            Sample::from_record(record, winning_tokens)
+                .ok_or_else(|| Error::InvalidLine(record.position.clone()))
+        })
+        .collect()
+}
+
+/// Number of features of boards of `winning_tokens`.
+fn feature_count(winning_tokens: i32) -> usize {
+    2 * winning_tokens.max(2) as usize
+}
+
+/// Window-pattern features of a `width` x `height` board whose cells are given by `cell`:
+/// the features of Red followed by those of Yellow.
+fn features(
+    width: i32,
+    height: i32,
+    winning_tokens: i32,
+    cell: impl Fn(Point) -> Symbol,
+) -> Vec<f32> {
+    let per_player = feature_count(winning_tokens) / 2;
+    let mut features = vec![0.; 2 * per_player];
+    // Right, down, and both diagonals.
+    for (dx, dy) in [(1, 0), (0, 1), (1, 1), (1, -1)] {
+        for x in 0..width {
+            for y in 0..height {
+                let (end_x, end_y) = (x + dx * (winning_tokens - 1), y + dy * (winning_tokens - 1));
+                if !(0..width).contains(&end_x) || !(0..height).contains(&end_y) {
+                    continue;
+                }
+                let mut counts = [0, 0];
+                let mut empty = None;
+                for i in 0..winning_tokens {
+                    let point = Point::new(x + dx * i, y + dy * i);
+                    match cell(point) {
+                        Symbol::Red => counts[0] += 1,
+                        Symbol::Yellow => counts[1] += 1,
+                        Symbol::Empty => empty = Some(point),
+                    }
+                }
+                for (player, tokens) in counts.into_iter().enumerate() {
+                    if tokens == 0 || counts[1 - player] != 0 || tokens == winning_tokens {
+                        continue;
+                    }
+                    let offset = player * per_player;
+                    let index = if tokens < winning_tokens - 1 {
+                        tokens as usize - 1
+                    } else {
+                        // Rows are numbered from 1 at the bottom.
+                        let row = height - empty.unwrap().y;
+                        per_player - 2 + (row % 2 == 0) as usize
+                    };
+                    features[offset + index] += FEATURE_SCALE;
+                }
+            }
+        }
+    }
+    features
+}
+
+/// Sum of `inputs` times `weights`, plus the bias that follows them.
+fn weighted_sum(weights: &[f32], inputs: &[f32]) -> f32 {
+    let (bias, weights) = weights.split_last().unwrap();
+    weights
+        .iter()
+        .zip(inputs)
+        .map(|(weight, input)| weight * input)
+        .sum::<f32>()
+        + bias
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid self-play data: {}", .0)]
+    SelfPlay(#[from] selfplay::Error),
+
+    #[error("Invalid network: {}", .0)]
+    InvalidLine(String),
+
+    #[error("Truncated network")]
+    Truncated,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connect4::board::Connect4;
+    use crate::connect4::engine::Engine;
+    use crate::shared::board::board_get_set::BoardGet;
+
+    use super::*;
+
+    #[test]
+    fn features() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for (column, symbol) in [(0, Symbol::Red), (1, Symbol::Red), (2, Symbol::Red)] {
+            board.play_column(symbol, column);
+        }
+        board.play_column(Symbol::Yellow, 0);
+        let features = super::features(7, 6, 4, |point| board.get(&point).symbol);
+        let counts: Vec<f32> = features.iter().map(|value| value / FEATURE_SCALE).collect();
+        // Red: windows of 1 and 2 tokens, then of 3 with the empty cell on an odd and on
+        // an even row. Yellow likewise.
+        assert_eq!(vec![6., 1., 1., 0., 3., 0., 0., 0.], counts);
+    }
+
+    #[test]
+    fn train() {
+        // Red wins the positions where it has more tokens in the bottom row.
+        let samples: Vec<Sample> = [(3, 0, 1.), (0, 3, -1.), (2, 1, 1.), (1, 2, -1.)]
+            .into_iter()
+            .map(|(red, yellow, target)| {
+                let features = super::features(7, 6, 4, |point| {
+                    if point.y < 5 {
+                        Symbol::Empty
+                    } else if point.x < red {
+                        Symbol::Red
+                    } else if point.x >= 7 - yellow {
+                        Symbol::Yellow
+                    } else {
+                        Symbol::Empty
+                    }
+                });
+                Sample { features, target }
+            })
+            .collect();
+        let mut mlp = Mlp::new(4, 8, 0);
+        let mut errors = vec![];
+        mlp.train(&samples, 200, 0.1, 0, |error| errors.push(error));
+        assert_eq!(200, errors.len());
+        assert!(errors[199] < errors[0] / 10.);
+        for sample in &samples {
+            assert_eq!(
+                sample.target.signum(),
+                mlp.predict(&sample.features).signum()
+            );
+        }
+    }
+
+    #[test]
+    fn read_write() {
+        let mlp = Mlp::new(4, 3, 1);
+        let mut bytes = vec![];
+        mlp.write(&mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+        assert!(text.starts_with("winning_tokens = 4\nhidden = 3\n"));
+        assert_eq!(mlp, Mlp::read(text.as_bytes()).unwrap());
+
+        let truncated = text.lines().take(4).collect::<Vec<_>>().join("\n");
+        assert!(matches!(
+            Mlp::read(truncated.as_bytes()),
+            Err(Error::Truncated)
+        ));
+        assert!(matches!(
+            Mlp::read("winning_tokens = 4\nhidden = 1\n1 2\n".as_bytes()),
+            Err(Error::InvalidLine(_))
+        ));
+    }
+
+    #[test]
+    fn search() {
+        // Red must block the 3 tokens of Yellow, whatever the network.
+        let mut board = Connect4::<7, 6>::new(4);
+        for (column, symbol) in [
+            (0, Symbol::Yellow),
+            (1, Symbol::Yellow),
+            (2, Symbol::Yellow),
+            (6, Symbol::Red),
+            (6, Symbol::Red),
+        ] {
+            board.play_column(symbol, column);
+        }
+        for bitboard in [true, false] {
+            let mut engine = Engine::new(1 << 16);
+            engine.bitboard = bitboard;
+            engine.evaluator = Some(std::sync::Arc::new(Mlp::new(4, 4, 0)));
+            let next_move = board.search(&mut engine, Symbol::Red, 2).unwrap();
+            assert_eq!(3, next_move.column);
+        }
+    }
+}
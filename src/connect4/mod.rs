@@ -1,6 +1,29 @@
+mod analysis;
+mod bitboard;
 mod board;
+mod book;
 mod cell;
+mod engine;
+mod eval;
 mod game;
+mod level;
+mod mcts;
+mod mlp;
+mod notation;
+mod ordering;
+mod personality;
 pub mod play;
+mod ponder;
+mod position;
+pub mod protocol;
+mod puzzle;
+mod selfplay;
+mod solver;
+mod stats;
 mod strategy;
 mod symbol;
+mod tally;
+mod threats;
+mod transposition;
+mod tuning;
+mod zobrist;
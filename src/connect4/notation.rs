@@ -0,0 +1,419 @@
+use std::collections::HashSet;
+
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+
+use super::board::Connect4;
+use super::position::Position;
+use super::symbol::Symbol;
+
+/// Writes `moves`, 0-based columns played since the empty board, as their 1-based numbers
+/// like `4453321`.
+pub fn write_moves(moves: &[i32]) -> String {
+    moves
+        .iter()
+        .map(|column| (column + 1).to_string())
+        .collect()
+}
+
+/// Reads the columns written by `write_moves`, ignoring spaces.
+///
+/// Only checks that the columns are digits from 1 to 9: `Connect4::from_moves` checks that
+/// they can be played.
+pub fn parse_moves(text: &str) -> Result<Vec<i32>, Error> {
+    text.chars()
+        .filter(|column| !column.is_whitespace())
+        .map(|column| match column.to_digit(10) {
+            Some(column @ 1..) => Ok(column as i32 - 1),
+            _ => Err(Error::InvalidColumn(column)),
+        })
+        .collect()
+}
+
+/// Cells written by `Connect4::to_grid`, rows from top to bottom, and the player to move.
+///
+/// Only checks the syntax of the grid: the rows may differ in width, and `grid_moves`
+/// checks that the discs can be played.
+pub fn parse_grid(text: &str) -> Result<(Vec<Vec<Symbol>>, Symbol), Error> {
+    let invalid = || Error::InvalidGrid(text.to_owned());
+    let (rows, to_move) = text.trim().split_once(' ').ok_or_else(invalid)?;
+    let to_move = match to_move.trim() {
+        "R" => Symbol::Red,
+        "Y" => Symbol::Yellow,
+        _ => return Err(invalid()),
+    };
+    let mut cells = vec![];
+    for (y, row) in rows.split('/').enumerate() {
+        let mut row_cells = vec![];
+        for cell in row.chars() {
+            match cell {
+                'R' => row_cells.push(Symbol::Red),
+                'Y' => row_cells.push(Symbol::Yellow),
+                '.' => row_cells.push(Symbol::Empty),
+                '1'..='9' => {
+                    let empty = cell.to_digit(10).unwrap() as usize;
+                    row_cells.extend(std::iter::repeat_n(Symbol::Empty, empty));
+                }
+                cell => return Err(Error::InvalidCell { row: y + 1, cell }),
+            }
+        }
+        cells.push(row_cells);
+    }
+    Ok((cells, to_move))
+}
+
+/// Columns played since the empty board that lead to the grid of `Connect4::to_grid`,
+/// Red first.
+///
+/// The grid is checked cell by cell before the moves are searched, so that an impossible
+/// grid gets the most precise error.
+pub fn grid_moves<const WIDTH: usize, const HEIGHT: usize>(
+    text: &str,
+    winning_tokens: i32,
+) -> Result<Vec<i32>, Error> {
+    let (cells, to_move) = parse_grid(text)?;
+    if cells.len() != HEIGHT {
+        return Err(Error::RowCount {
+            expected: HEIGHT,
+            found: cells.len(),
+        });
+    }
+    for (y, row) in cells.iter().enumerate() {
+        if row.len() != WIDTH {
+            return Err(Error::RowWidth {
+                row: y + 1,
+                expected: WIDTH,
+                found: row.len(),
+            });
+        }
+    }
+
+    for (y, rows) in cells.windows(2).enumerate() {
+        for (x, (cell, below)) in rows[0].iter().zip(&rows[1]).enumerate() {
+            if *cell != Symbol::Empty && *below == Symbol::Empty {
+                return Err(Error::FloatingDisc {
+                    row: y + 1,
+                    column: x + 1,
+                });
+            }
+        }
+    }
+    let count = |symbol| {
+        cells
+            .iter()
+            .flatten()
+            .filter(|cell| **cell == symbol)
+            .count()
+    };
+    let (red, yellow) = (count(Symbol::Red), count(Symbol::Yellow));
+    if red != yellow && red != yellow + 1 {
+        return Err(Error::DiscCount { red, yellow });
+    }
+    if (red == yellow) != (to_move == Symbol::Red) {
+        return Err(Error::SideToMove {
+            to_move,
+            red,
+            yellow,
+        });
+    }
+
+    let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+    let mut moves = vec![];
+    if !find_moves(
+        &mut board,
+        &cells,
+        Symbol::Red,
+        red + yellow,
+        &mut moves,
+        &mut HashSet::new(),
+    ) {
+        return Err(Error::Unreachable);
+    }
+    Ok(moves)
+}
+
+/// Searches the `tokens_left` moves leading from `board` to `cells`, `symbol` playing
+/// first, none of them ending the game but the last one. On success, `board` is left with
+/// the moves played.
+///
+/// `dead_ends` holds the hashes of the boards from which no moves lead to `cells`.
+fn find_moves<const WIDTH: usize, const HEIGHT: usize>(
+    board: &mut Connect4<WIDTH, HEIGHT>,
+    cells: &[Vec<Symbol>],
+    symbol: Symbol,
+    tokens_left: usize,
+    moves: &mut Vec<i32>,
+    dead_ends: &mut HashSet<u64>,
+) -> bool {
+    if tokens_left == 0 {
+        return true;
+    }
+    if dead_ends.contains(&board.hash()) {
+        return false;
+    }
+    for column in 0..WIDTH as i32 {
+        let Some(play_column) = board.play_column(symbol, column) else {
+            continue;
+        };
+        let Point { x, y } = play_column.position;
+        if cells[y as usize][x as usize] == symbol
+            && (!play_column.outcome.is_over() || tokens_left == 1)
+        {
+            moves.push(column);
+            if find_moves(
+                board,
+                cells,
+                symbol.other(),
+                tokens_left - 1,
+                moves,
+                dead_ends,
+            ) {
+                return true;
+            }
+            moves.pop();
+        }
+        play_column.undo(board);
+    }
+    dead_ends.insert(board.hash());
+    false
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// Board after the moves written like `4453321`, and the player to move.
+    pub fn from_moves(text: &str, winning_tokens: i32) -> Result<(Self, Symbol), Error> {
+        let (board, _, to_move) = Self::replay(winning_tokens, &parse_moves(text)?)?;
+        Ok((board, to_move))
+    }
+
+    /// Board written like `Connect4::to_grid`, and the player to move.
+    pub fn from_grid(text: &str, winning_tokens: i32) -> Result<(Self, Symbol), Error> {
+        let (board, _, to_move) = Self::replay(
+            winning_tokens,
+            &grid_moves::<WIDTH, HEIGHT>(text, winning_tokens)?,
+        )?;
+        Ok((board, to_move))
+    }
+
+    /// Plays `moves`, 0-based columns, from the empty board, and returns the board with the
+    /// outcome of the last move and the player to move.
+    pub fn replay(
+        winning_tokens: i32,
+        moves: &[i32],
+    ) -> Result<(Self, GameOutcome<Symbol>, Symbol), Error> {
+        let mut board = Self::new(winning_tokens);
+        let mut outcome = GameOutcome::Ongoing;
+        let mut symbol = Symbol::Red;
+        for (i, column) in moves.iter().enumerate() {
+            let index = i + 1;
+            if outcome.is_over() {
+                return Err(Error::GameOver { index });
+            }
+            if !(0..WIDTH as i32).contains(column) {
+                return Err(Error::NoColumn {
+                    index,
+                    column: column + 1,
+                });
+            }
+            outcome = board
+                .play_column(symbol, *column)
+                .ok_or(Error::FullColumn {
+                    index,
+                    column: column + 1,
+                })?
+                .outcome;
+            symbol = symbol.other();
+        }
+        Ok((board, outcome, symbol))
+    }
+
+    /// Writes the board like FEN: the rows from top to bottom separated by `/`, each cell
+    /// `R`, `Y` or a digit counting the empty cells in a row, then `R` or `Y` to move, like
+    /// `7/7/7/7/3Y3/2RR3 Y`.
+    pub fn to_grid(&self, to_move: Symbol) -> String {
+        let rows: Vec<String> = (0..HEIGHT as i32)
+            .map(|y| {
+                let mut row = String::new();
+                let mut empty = 0;
+                for x in 0..WIDTH as i32 {
+                    let cell = match self.get(&Point::new(x, y)).symbol {
+                        Symbol::Empty => {
+                            empty += 1;
+                            continue;
+                        }
+                        Symbol::Red => 'R',
+                        Symbol::Yellow => 'Y',
+                    };
+                    if empty > 0 {
+                        row += &empty.to_string();
+                        empty = 0;
+                    }
+                    row.push(cell);
+                }
+                if empty > 0 {
+                    row += &empty.to_string();
+                }
+                row
+            })
+            .collect();
+        let to_move = if to_move == Symbol::Yellow { 'Y' } else { 'R' };
+        format!("{} {to_move}", rows.join("/"))
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq)]
+pub enum Error {
+    #[error("Invalid column '{}', expected 1 to 9", .0)]
+    InvalidColumn(char),
+
+    #[error("Move {index}: there is no column {column}")]
+    NoColumn { index: usize, column: i32 },
+
+    #[error("Move {index}: column {column} is full")]
+    FullColumn { index: usize, column: i32 },
+
+    #[error("Move {index}: the game is already over")]
+    GameOver { index: usize },
+
+    #[error("Invalid grid: {}, expected the rows from top to bottom separated by '/', then R or Y to move", .0)]
+    InvalidGrid(String),
+
+    #[error("Expected {expected} rows, found {found}")]
+    RowCount { expected: usize, found: usize },
+
+    #[error("Row {row}: expected {expected} cells, found {found}")]
+    RowWidth {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("Row {row}: invalid cell '{cell}', expected R, Y, . or a number of empty cells")]
+    InvalidCell { row: usize, cell: char },
+
+    #[error("Row {row}, column {column}: the disc floats above an empty cell")]
+    FloatingDisc { row: usize, column: usize },
+
+    #[error("Impossible disc count: {red} red and {yellow} yellow, Red playing first")]
+    DiscCount { red: usize, yellow: usize },
+
+    #[error("{to_move:?} can't be to move with {red} red and {yellow} yellow discs")]
+    SideToMove {
+        to_move: Symbol,
+        red: usize,
+        yellow: usize,
+    },
+
+    #[error("No moves lead to the grid without ending the game before the last one")]
+    Unreachable,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moves() {
+        assert_eq!(vec![3, 3, 4, 2, 2, 1, 0], parse_moves("4453321").unwrap());
+        assert_eq!("4453321", write_moves(&[3, 3, 4, 2, 2, 1, 0]));
+        assert_eq!(vec![3, 3], parse_moves("4 4").unwrap());
+        assert_eq!(Err(Error::InvalidColumn('0')), parse_moves("40"));
+        assert_eq!(Err(Error::InvalidColumn('a')), parse_moves("4a"));
+
+        let (board, to_move) = Connect4::<7, 6>::from_moves("4453321", 4).unwrap();
+        assert_eq!(Symbol::Yellow, to_move);
+        assert_eq!("7/7/7/7/2RY3/RYYRR2 Y", board.to_grid(to_move));
+
+        let error = |moves| Connect4::<5, 4>::from_moves(moves, 3).err().unwrap();
+        assert_eq!(
+            Error::NoColumn {
+                index: 2,
+                column: 6
+            },
+            error("16")
+        );
+        assert_eq!(
+            Error::FullColumn {
+                index: 5,
+                column: 1
+            },
+            error("11111")
+        );
+        assert_eq!(Error::GameOver { index: 6 }, error("121213"));
+        assert_eq!(
+            "Move 6: the game is already over",
+            Error::GameOver { index: 6 }.to_string()
+        );
+    }
+
+    #[test]
+    fn grid() {
+        let (board, to_move) = Connect4::<7, 6>::from_moves("4453321", 4).unwrap();
+        let grid = board.to_grid(to_move);
+        let (from_grid, grid_to_move) = Connect4::<7, 6>::from_grid(&grid, 4).unwrap();
+        assert_eq!(to_move, grid_to_move);
+        assert_eq!(board.hash(), from_grid.hash());
+        assert_eq!(grid, from_grid.to_grid(to_move));
+
+        let empty = Connect4::<7, 6>::new(4);
+        assert_eq!("7/7/7/7/7/7 R", empty.to_grid(Symbol::Red));
+        assert_eq!(Ok(vec![]), grid_moves::<7, 6>("......./7/7/7/7/7 R", 4));
+        let (cells, to_move) = parse_grid("2/R1 Y").unwrap();
+        assert_eq!(Symbol::Yellow, to_move);
+        assert_eq!(
+            vec![vec![Symbol::Empty; 2], vec![Symbol::Red, Symbol::Empty]],
+            cells
+        );
+
+        // A win can only be the last move.
+        let (board, _) = Connect4::<5, 4>::from_moves("12121", 3).unwrap();
+        let moves = grid_moves::<5, 4>(&board.to_grid(Symbol::Yellow), 3).unwrap();
+        assert_eq!(Some(&0), moves.last());
+    }
+
+    #[test]
+    fn grid_errors() {
+        let error = |grid| grid_moves::<5, 4>(grid, 3).err().unwrap();
+        assert!(matches!(error("5/5/5/5"), Error::InvalidGrid(_)));
+        assert!(matches!(error("5/5/5/5 B"), Error::InvalidGrid(_)));
+        assert_eq!(
+            Error::RowCount {
+                expected: 4,
+                found: 3
+            },
+            error("5/5/5 R")
+        );
+        assert_eq!(
+            Error::RowWidth {
+                row: 2,
+                expected: 5,
+                found: 6
+            },
+            error("5/R5/5/5 R")
+        );
+        assert_eq!(
+            Error::InvalidCell { row: 4, cell: 'x' },
+            error("5/5/5/x4 R")
+        );
+        assert_eq!(
+            Error::FloatingDisc { row: 3, column: 2 },
+            error("5/5/1R3/5 Y")
+        );
+        assert_eq!(
+            Error::DiscCount { red: 3, yellow: 0 },
+            error("5/5/5/RRR2 Y")
+        );
+        assert_eq!(Error::DiscCount { red: 0, yellow: 1 }, error("5/5/5/Y4 R"));
+        assert_eq!(
+            Error::SideToMove {
+                to_move: Symbol::Red,
+                red: 1,
+                yellow: 0
+            },
+            error("5/5/5/R4 R")
+        );
+        // Both players completed a line.
+        assert_eq!(Error::Unreachable, error("5/RY3/RY3/RY3 R"));
+        assert_eq!(Error::Unreachable, error("5/R4/RY3/RYY2 R"));
+    }
+}
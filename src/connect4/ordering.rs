@@ -0,0 +1,156 @@
+use std::cmp::Ordering;
+
+use super::game::PlayColumn;
+use super::symbol::Symbol;
+
+/// Heuristic telling which columns to search first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Heuristic {
+    /// The best column found by a previous search of the position.
+    TableMove,
+
+    /// The columns that caused a cutoff in the other positions at the same depth, most
+    /// recent first.
+    Killers,
+
+    /// The columns whose token scores the most by itself.
+    Score,
+
+    /// The cells whose token caused the most cutoffs so far, weighted by the depth left to
+    /// search.
+    History,
+
+    /// The columns closest to the center, whose tokens belong to more lines.
+    Center,
+}
+
+/// Order in which the columns of a position are searched.
+///
+/// Columns are compared by each heuristic in turn, each heuristic only breaking the ties of
+/// the previous ones. Without heuristics, columns are searched from left to right.
+pub struct MoveOrdering {
+    pub heuristics: Vec<Heuristic>,
+
+    /// Two killer columns for each depth left to search.
+    killers: Vec<[Option<i32>; 2]>,
+
+    /// Cutoffs caused by each cell, for each player.
+    history: Vec<u32>,
+
+    /// Width of the board of the current search.
+    width: i32,
+
+    /// How many of the first columns of the root of a search are moved after the other ones,
+    /// so that the helper threads of a search don't all start with the same column.
+    pub(super) rotation: usize,
+}
+
+impl MoveOrdering {
+    pub fn new(heuristics: Vec<Heuristic>) -> Self {
+        Self {
+            heuristics,
+            killers: vec![],
+            history: vec![],
+            width: 0,
+            rotation: 0,
+        }
+    }
+
+    /// Prepares a search of a `width` x `height` board.
+    ///
+    /// Killers only make sense within a search, while the history of previous searches is
+    /// kept but weighs less.
+    pub(super) fn new_search(&mut self, width: i32, height: i32) {
+        self.killers.clear();
+        let len = 2 * (width * height) as usize;
+        if self.width != width || self.history.len() != len {
+            self.width = width;
+            self.history = vec![0; len];
+        } else {
+            self.history.iter_mut().for_each(|cutoffs| *cutoffs /= 2);
+        }
+    }
+
+    /// Sorts `play_columns` of `symbol`, with `depth` moves left to search, best first.
+    pub(super) fn sort(
+        &self,
+        play_columns: &mut [PlayColumn],
+        symbol: Symbol,
+        depth: i32,
+        table_column: Option<i32>,
+    ) {
+        let killers = self
+            .killers
+            .get(depth as usize)
+            .copied()
+            .unwrap_or_default();
+        let key = |heuristic: &Heuristic, play_column: &PlayColumn| {
+            let column = play_column.position.x;
+            match heuristic {
+                Heuristic::TableMove => (Some(column) != table_column) as i64,
+                Heuristic::Killers => killers
+                    .iter()
+                    .position(|killer| *killer == Some(column))
+                    .unwrap_or(killers.len()) as i64,
+                Heuristic::Score => -play_column.delta_score as i64,
+                Heuristic::History => -(self.history_cutoffs(symbol, play_column) as i64),
+                Heuristic::Center => (2 * column - (self.width - 1)).abs() as i64,
+            }
+        };
+        play_columns.sort_by(|a, b| {
+            self.heuristics
+                .iter()
+                .map(|heuristic| key(heuristic, a).cmp(&key(heuristic, b)))
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+    }
+
+    /// Records that `play_column` of `symbol` caused a cutoff, with `depth` moves left to
+    /// search.
+    pub(super) fn cutoff(&mut self, symbol: Symbol, depth: i32, play_column: &PlayColumn) {
+        let column = play_column.position.x;
+        let depth = depth as usize;
+        if self.killers.len() <= depth {
+            self.killers.resize(depth + 1, Default::default());
+        }
+        let killers = &mut self.killers[depth];
+        if killers[0] != Some(column) {
+            *killers = [Some(column), killers[0]];
+        }
+        let index = self.history_index(symbol, play_column);
+        if let Some(cutoffs) = index.and_then(|index| self.history.get_mut(index)) {
+            *cutoffs = cutoffs.saturating_add((depth * depth) as u32);
+        }
+    }
+
+    fn history_cutoffs(&self, symbol: Symbol, play_column: &PlayColumn) -> u32 {
+        self.history_index(symbol, play_column)
+            .and_then(|index| self.history.get(index))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    fn history_index(&self, symbol: Symbol, play_column: &PlayColumn) -> Option<usize> {
+        let player = match symbol {
+            Symbol::Red => 0,
+            Symbol::Yellow => 1,
+            Symbol::Empty => return None,
+        };
+        let cells = self.history.len() / 2;
+        let position = play_column.position;
+        Some(player * cells + (position.y * self.width + position.x) as usize)
+    }
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        Self::new(vec![
+            Heuristic::TableMove,
+            Heuristic::Score,
+            Heuristic::Killers,
+            Heuristic::History,
+            Heuristic::Center,
+        ])
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use crate::shared::rng::Rng;
+
+use super::strategy::Move;
+
+/// How the computer chooses its move among the columns it searched.
+///
+/// Except for `Best`, every column gets its exact score from `Connect4::analyze`, and the
+/// random choices come from the generator of the engine, so that the same seed plays the
+/// same game.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Personality {
+    /// The best move, the first column among equally good ones.
+    #[default]
+    Best,
+
+    /// A random move among those scoring at most `margin` less than the best one.
+    NearBest { margin: i32 },
+
+    /// A random move, with a probability proportional to `exp(score / temperature)`.
+    Softmax { temperature: f64 },
+
+    /// The best move, except for a share `rate` of the moves where it plays a random worse
+    /// one.
+    Blunder { rate: f64 },
+}
+
+impl Personality {
+    /// Chooses one of `moves`, sorted best first like those of `Connect4::analyze`, or
+    /// returns `None` if there are none.
+    pub fn choose(&self, mut moves: Vec<Move>, rng: &mut Rng) -> Option<Move> {
+        let best = moves.first()?.score;
+        let index = match *self {
+            Personality::Best => 0,
+            Personality::NearBest { margin } => {
+                let near = moves
+                    .iter()
+                    .take_while(|next_move| next_move.score >= best.saturating_sub(margin))
+                    .count();
+                rng.below(near)
+            }
+            Personality::Softmax { temperature } => {
+                // Relative to the best score, so that the weights don't overflow.
+                let weights: Vec<f64> = moves
+                    .iter()
+                    .map(|next_move| {
+                        ((next_move.score as f64 - best as f64)
+                            / temperature.max(f64::MIN_POSITIVE))
+                        .exp()
+                    })
+                    .collect();
+                let mut target = rng.unit() * weights.iter().sum::<f64>();
+                weights
+                    .iter()
+                    .position(|weight| {
+                        target -= weight;
+                        target < 0.
+                    })
+                    .unwrap_or(0)
+            }
+            Personality::Blunder { rate } => {
+                let worse = moves
+                    .iter()
+                    .filter(|next_move| next_move.score < best)
+                    .count();
+                if worse > 0 && rng.unit() < rate {
+                    moves.len() - worse + rng.below(worse)
+                } else {
+                    0
+                }
+            }
+        };
+        Some(moves.swap_remove(index))
+    }
+}
+
+/// Written `best`, `near=<margin>`, `softmax=<temperature>` or `blunder=<rate>`.
+impl Display for Personality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Personality::Best => write!(f, "best"),
+            Personality::NearBest { margin } => write!(f, "near={margin}"),
+            Personality::Softmax { temperature } => write!(f, "softmax={temperature}"),
+            Personality::Blunder { rate } => write!(f, "blunder={rate}"),
+        }
+    }
+}
+
+impl FromStr for Personality {
+    type Err = Error;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidPersonality(text.to_owned());
+        let (name, value) = text.split_once('=').unwrap_or((text, ""));
+        let number = || value.parse::<f64>().ok().filter(|value| *value >= 0.);
+        match name {
+            "best" if value.is_empty() => Ok(Personality::Best),
+            "near" => Ok(Personality::NearBest {
+                margin: value
+                    .parse()
+                    .ok()
+                    .filter(|margin| *margin >= 0)
+                    .ok_or_else(invalid)?,
+            }),
+            "softmax" => Ok(Personality::Softmax {
+                temperature: number()
+                    .filter(|temperature| *temperature > 0.)
+                    .ok_or_else(invalid)?,
+            }),
+            "blunder" => Ok(Personality::Blunder {
+                rate: number().filter(|rate| *rate <= 1.).ok_or_else(invalid)?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid personality: {}, expected best, near=<margin>, softmax=<temperature> or blunder=<rate>", .0)]
+    InvalidPersonality(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connect4::board::Connect4;
+    use crate::connect4::engine::Engine;
+    use crate::connect4::symbol::Symbol;
+    use crate::shared::game_outcome::GameOutcome;
+
+    use super::*;
+
+    /// Columns chosen among moves scoring `scores`, over many draws.
+    fn chosen(personality: Personality, scores: &[i32]) -> Vec<i32> {
+        let mut rng = Rng::new(0);
+        let mut chosen: Vec<i32> = (0..200)
+            .map(|_| {
+                let moves = scores
+                    .iter()
+                    .enumerate()
+                    .map(|(column, score)| Move {
+                        column: column as i32,
+                        score: *score,
+                        outcome: GameOutcome::Ongoing,
+                        pv: vec![column as i32],
+                    })
+                    .collect();
+                personality.choose(moves, &mut rng).unwrap().column
+            })
+            .collect();
+        chosen.sort();
+        chosen.dedup();
+        chosen
+    }
+
+    #[test]
+    fn choose() {
+        let scores = [50, 50, 40, 0, -100];
+        assert_eq!(vec![0], chosen(Personality::Best, &scores));
+        assert_eq!(
+            vec![0, 1],
+            chosen(Personality::NearBest { margin: 0 }, &scores)
+        );
+        assert_eq!(
+            vec![0, 1, 2],
+            chosen(Personality::NearBest { margin: 10 }, &scores)
+        );
+        assert_eq!(
+            vec![0, 1],
+            chosen(Personality::Softmax { temperature: 0.1 }, &scores)
+        );
+        assert_eq!(
+            vec![0, 1, 2, 3, 4],
+            chosen(Personality::Softmax { temperature: 1e6 }, &scores)
+        );
+        assert_eq!(vec![0], chosen(Personality::Blunder { rate: 0. }, &scores));
+        assert_eq!(
+            vec![2, 3, 4],
+            chosen(Personality::Blunder { rate: 1. }, &scores)
+        );
+        assert_eq!(
+            None,
+            Personality::Best
+                .choose(vec![], &mut Rng::new(0))
+                .map(|next_move| next_move.column)
+        );
+    }
+
+    #[test]
+    fn same_seed() {
+        let play = |seed| {
+            let mut engine = Engine::new(1 << 16);
+            engine.personality = Personality::Softmax {
+                temperature: 10000.,
+            };
+            engine.rng = Rng::new(seed);
+            let mut board = Connect4::<5, 4>::new(3);
+            let mut symbol = Symbol::Red;
+            let mut columns = vec![];
+            loop {
+                let next_move = board.next_move_with(&mut engine, symbol, 2).unwrap();
+                columns.push(next_move.column);
+                if next_move.outcome.is_over() {
+                    return columns;
+                }
+                symbol = symbol.other();
+            }
+        };
+        assert_eq!(play(1), play(1));
+        assert!((2..10).any(|seed| play(seed) != play(1)));
+    }
+
+    #[test]
+    fn parse() {
+        for personality in [
+            Personality::Best,
+            Personality::NearBest { margin: 20000 },
+            Personality::Softmax { temperature: 1.5 },
+            Personality::Blunder { rate: 0.25 },
+        ] {
+            assert_eq!(
+                personality,
+                personality.to_string().parse::<Personality>().unwrap()
+            );
+        }
+        for text in ["", "best=1", "near=-1", "softmax=0", "blunder=2", "random"] {
+            assert!(text.parse::<Personality>().is_err(), "{text}");
+        }
+    }
+}
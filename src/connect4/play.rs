@@ -1,11 +1,94 @@
-use crate::shared::board::board_all_points::BoardAllPoints;
-use crate::shared::board::board_get_set::BoardGet;
+use std::io::Read;
+use std::time::Duration;
+
 use crate::shared::board::board_size::BoardSize;
 use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+use crate::shared::rng;
+use crate::shared::rng::Rng;
 
+use super::book;
+use super::book::Book;
+use super::engine::Engine;
+use super::eval;
+use super::eval::EvalParams;
+use super::level;
+pub use super::level::Level;
+use super::mcts::Mcts;
+pub use super::mcts::MctsBudget;
+use super::mlp;
+use super::mlp::Mlp;
+use super::notation;
+pub use super::personality::Personality;
+use super::ponder::Ponder;
+use super::position::Position;
+use super::protocol;
+use super::puzzle;
+use super::puzzle::Puzzle;
+use super::puzzle::Verdict;
+use super::selfplay::SelfPlay;
+use super::solver::Solver;
+use super::strategy::Move;
 use super::symbol::Symbol;
+use super::tuning::Tuner;
+
+/// Opening book built by `build_connect4_book` and used by `play_connect4`.
+const BOOK_FILE: &str = "connect4.book";
+
+/// Weights of the evaluation tuned by `tune_connect4` and used by `play_connect4`.
+const PARAMS_FILE: &str = "connect4.params";
+
+/// Puzzles generated by `generate_connect4_puzzles` and played by `solve_connect4_puzzles`.
+const PUZZLES_FILE: &str = "connect4.puzzles";
+
+/// How many random games are tried for each puzzle generated.
+const PUZZLE_GAMES: usize = 1000;
+
+/// Positions of the games played by `export_connect4_games`.
+const GAMES_FILE: &str = "connect4.tsv";
+
+/// Evaluation trained by `train_connect4_evaluator`.
+const MLP_FILE: &str = "connect4.mlp";
+
+/// Hidden neurons of a new evaluation network.
+const MLP_HIDDEN: usize = 16;
+
+/// Learning rate of the training of the evaluation network.
+const LEARNING_RATE: f32 = 0.01;
+
+/// Calibration of the levels measured by `calibrate_connect4_levels`.
+const LEVELS_FILE: &str = "connect4.levels";
+
+/// How many moves ahead the columns are searched by the analysis of the board.
+const ANALYSIS_DEPTH: i32 = 8;
+
+/// How long the computer thinks before playing.
+pub enum Thinking {
+    /// Searches as deep as possible within the given time.
+    Time(Duration),
+
+    /// Plays the move of a Monte Carlo tree search.
+    Mcts(MctsBudget),
 
-pub fn play_connect4(difficulty: i32, winning_tokens: i32) -> Result<(), Error> {
+    /// Searches and solves like the given level.
+    Level(Level),
+}
+
+/// Plays Connect4 against the computer, which searches its moves on `threads` threads and
+/// chooses them according to `personality`.
+///
+/// The random choices of the computer come from `seed`: on one thread, with the same moves
+/// of the human, the same seed plays the same game. For this, the computer only thinks
+/// while the human does when it plays the best move.
+///
+/// Returns the outcome of the game, `Ongoing` if the input ended first.
+pub fn play_connect4(
+    thinking: Thinking,
+    winning_tokens: i32,
+    threads: usize,
+    personality: Personality,
+    seed: u64,
+) -> Result<GameOutcome<Symbol>, Error> {
     println!("Welcome to Connect4!");
 
     let me = Symbol::Red;
@@ -13,15 +96,116 @@ pub fn play_connect4(difficulty: i32, winning_tokens: i32) -> Result<(), Error>
     println!("Computer is playing '{:?}'", me.other());
 
     let mut board = super::board::Connect4::<7, 6>::new(winning_tokens);
+    // Columns played since the empty board, to write the position down.
+    let mut moves = vec![];
+    let mut engine = Engine::default();
+    engine.threads = threads;
+    engine.personality = personality;
+    engine.rng = Rng::new(seed);
+    let mut mcts = Mcts::new(seed);
+    let mut solver = Solver::default();
+    if let Thinking::Level(level) = thinking {
+        println!("Computer is playing at level '{level}'");
+        match level::load_calibration(LEVELS_FILE) {
+            Ok(calibration) => {
+                if let Some(calibration) = calibration
+                    .iter()
+                    .find(|calibration| calibration.level == level)
+                {
+                    println!(
+                        "It won {:.1}% of the points against the previous level",
+                        calibration.score * 100.
+                    );
+                }
+            }
+            Err(level::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => println!("Unable to load calibration {LEVELS_FILE}: {err}"),
+        }
+    }
+    if personality != Personality::Best {
+        println!("Computer is playing '{personality}', seed {seed}");
+    }
+    let mut show_stats = false;
+    // Search of the expected reply of the human, while they think.
+    let mut ponder: Option<Ponder> = None;
+    match Book::load::<7, 6>(BOOK_FILE) {
+        Ok(book) => {
+            println!("Loaded opening book with {} positions", book.len());
+            engine.book = Some(book);
+        }
+        Err(book::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => println!("Unable to load opening book {BOOK_FILE}: {err}"),
+    }
+    match EvalParams::load(PARAMS_FILE) {
+        Ok(params) => {
+            println!("Loaded evaluation weights {params:?}");
+            engine.params = params;
+        }
+        Err(eval::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => (),
+        Err(err) => println!("Unable to load evaluation weights {PARAMS_FILE}: {err}"),
+    }
 
     println!("Board is now:");
     println!("{}", board.view_as_xo());
 
-    println!("Player '{:?}' choose a column", me);
+    println!("Player '{:?}' choose a column, or type:", me);
+    println!("- 's' to solve the board");
+    println!("- 'a' to analyze the board");
+    println!("- 't' to see the threats");
+    println!("- 'i' to show or hide the search statistics");
+    println!("- 'd' to agree to a draw once no line can be completed");
+    println!("- 'f' to write down the position, as moves and as a grid");
 
     let lines = std::io::stdin().lines();
     for line in lines {
         let line = line?.to_ascii_uppercase();
+        if line == "T" {
+            for symbol in [me, me.other()] {
+                println!(
+                    "Threats of '{:?}': '!' now, '1' on odd rows, '2' on even rows",
+                    symbol
+                );
+                println!("{}", board.view_threats(symbol));
+            }
+            continue;
+        }
+        if line == "I" {
+            show_stats = !show_stats;
+            println!(
+                "Search statistics {}",
+                if show_stats { "shown" } else { "hidden" }
+            );
+            continue;
+        }
+        if line == "D" {
+            if board.is_dead() {
+                print_outcome(&GameOutcome::Draw);
+                return Ok(GameOutcome::Draw);
+            }
+            println!("A line can still be completed, keep playing");
+            continue;
+        }
+        if line == "F" {
+            println!("Moves: {}", notation::write_moves(&moves));
+            println!("Grid: {}", board.to_grid(me));
+            continue;
+        }
+        if line == "A" {
+            let moves = board.analyze(&mut engine, me, ANALYSIS_DEPTH);
+            println!("{}", board.view_analysis(&moves));
+            continue;
+        }
+        if line == "S" {
+            if let Some(solution) = solver.solve(&board, me) {
+                println!(
+                    "{:?} to play: {} ({} positions searched)",
+                    me,
+                    solution,
+                    solver.nodes()
+                );
+            }
+            continue;
+        }
         let column = {
             match line.parse::<i32>() {
                 Ok(column) => column - 1,
@@ -39,41 +223,366 @@ pub fn play_connect4(difficulty: i32, winning_tokens: i32) -> Result<(), Error>
             continue;
         }
         if let Some(my_move) = board.play_column(me, column) {
+            moves.push(column);
             println!("{:?} Playing {:?}", me, my_move.position.x + 1);
+            if let Some(ponder) = ponder.take() {
+                match ponder.stop(column) {
+                    Some(pondered) => {
+                        println!(
+                            "Expected move, searched {} moves ahead while you were thinking, best reply {}",
+                            pondered.depth + 1,
+                            pondered.next_move.column + 1
+                        );
+                        if show_stats {
+                            println!("Search statistics: {}", pondered.stats);
+                        }
+                    }
+                    None if show_stats => println!("Unexpected move, search discarded"),
+                    None => (),
+                }
+            }
 
             println!("Board is now:");
             println!("{}", board.view_as_xo());
 
-            if my_move.end_of_game {
-                println!("{:?} won!!", me);
-                break;
+            if my_move.outcome.is_over() {
+                print_outcome(&my_move.outcome);
+                return Ok(my_move.outcome);
             }
+            offer_draw(&board);
         } else {
             println!("Please choose a different column");
             continue;
         }
 
-        if let Some(opponent_move) = board.next_move(me.other(), difficulty) {
+        engine.stats = Default::default();
+        let opponent_move =
+            match thinking {
+                Thinking::Time(budget) => board
+                    .next_move_timed(&mut engine, me.other(), budget)
+                    .map(|timed_move| {
+                        println!("Searched {} moves ahead", timed_move.depth + 1);
+                        timed_move.next_move
+                    }),
+                Thinking::Mcts(budget) => {
+                    board
+                        .next_move_mcts(&mut mcts, me.other(), budget)
+                        .map(|mcts_move| {
+                            for root_move in &mcts_move.root_moves {
+                                println!(
+                                    "Column {}: {} playouts, {:.1}% won",
+                                    root_move.column + 1,
+                                    root_move.visits,
+                                    root_move.win_rate * 100.
+                                );
+                            }
+                            Move {
+                                column: mcts_move.column,
+                                score: 0,
+                                outcome: mcts_move.outcome,
+                                pv: vec![mcts_move.column],
+                            }
+                        })
+                }
+                Thinking::Level(level) => {
+                    level.next_move(&mut board, &mut engine, &mut solver, me.other())
+                }
+            };
+        if let Some(opponent_move) = opponent_move {
+            moves.push(opponent_move.column);
             println!("{:?} Playing {:?}", me.other(), opponent_move.column + 1);
+            if opponent_move.pv.len() > 1 {
+                let line: Vec<String> = opponent_move
+                    .pv
+                    .iter()
+                    .map(|column| (column + 1).to_string())
+                    .collect();
+                println!("Expecting {}", line.join(" "));
+            }
+            if engine.stats.nodes > 0 {
+                println!("Searched {} positions", engine.stats.nodes);
+                if show_stats {
+                    println!("Search statistics: {}", engine.stats);
+                    println!(
+                        "Evaluation of the board for {:?}: {}",
+                        me.other(),
+                        board.evaluate(me.other())
+                    );
+                    println!(
+                        "Transposition table: {} hits out of {} probes ({:.1}%)",
+                        engine.table.hits(),
+                        engine.table.probes(),
+                        engine.table.hit_rate() * 100.
+                    );
+                }
+            }
 
             println!("Board is now:");
             println!("{}", board.view_as_xo());
 
-            if opponent_move.end_of_game {
-                println!("{:?} won!!", me.other());
-                break;
+            if opponent_move.outcome.is_over() {
+                print_outcome(&opponent_move.outcome);
+                return Ok(opponent_move.outcome);
+            }
+            offer_draw(&board);
+
+            if !matches!(thinking, Thinking::Mcts(_)) {
+                if let Some(&expected) = opponent_move.pv.get(1) {
+                    ponder = Ponder::start(&board, &engine, me.other(), expected);
+                }
             }
         }
+    }
+
+    Ok(GameOutcome::Ongoing)
+}
+
+/// Offers a draw when no line can be completed anymore, rather than filling the board.
+fn offer_draw(board: &impl Position) {
+    if board.is_dead() {
+        println!("No line can be completed anymore, type 'd' to agree to a draw");
+    }
+}
 
-        if board
-            .all_points()
-            .map(|point| board.get(&point))
-            .all(|cell| cell.symbol != Symbol::Empty)
-        {
-            return Err(Error::EOG);
+/// Tells who won and with which line, or that the game is a draw.
+fn print_outcome(outcome: &GameOutcome<Symbol>) {
+    match outcome {
+        GameOutcome::Win { winner, line } => {
+            let [first, last] =
+                line.map(|point| format!("{}{}", point.x + 1, (b'A' + point.y as u8) as char));
+            println!("{winner:?} won!! ({first} to {last})");
         }
+        GameOutcome::Draw => println!("Draw!!"),
+        GameOutcome::Ongoing => (),
     }
+}
 
+/// Searches the first `plies` moves of Connect4 games `depth` moves ahead, and saves them
+/// as the opening book.
+pub fn build_connect4_book(plies: i32, depth: i32, winning_tokens: i32) -> Result<(), book::Error> {
+    println!("Building opening book, {plies} moves searched {depth} moves ahead");
+    let mut engine = Engine::default();
+    let book = Book::build::<7, 6>(&mut engine, winning_tokens, plies, depth, |positions| {
+        if positions % 1000 == 0 {
+            println!("{positions} positions searched");
+        }
+    });
+    book.save(BOOK_FILE)?;
+    println!("Saved {} positions to {BOOK_FILE}", book.len());
+    Ok(())
+}
+
+/// Tunes the weights of the evaluation by `rounds` rounds of self-play, `depth` moves ahead,
+/// and saves them for `play_connect4`.
+///
+/// The tuning starts from the saved weights, if any.
+pub fn tune_connect4(rounds: usize, depth: i32, winning_tokens: i32) -> Result<(), eval::Error> {
+    let params = match EvalParams::load(PARAMS_FILE) {
+        Err(eval::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+            EvalParams::default()
+        }
+        params => params?,
+    };
+    println!("Tuning {params:?}, {rounds} rounds of games searched {depth} moves ahead");
+    let mut tuner = Tuner::new(winning_tokens, depth);
+    let mut round = 0;
+    let params = tuner.tune::<7, 6>(params, rounds, |result| {
+        round += 1;
+        match result.score {
+            Some(score) => println!(
+                "Round {round}: {:?} won {:.1}% of the points",
+                result.params,
+                score * 100.
+            ),
+            None => println!("Round {round}: {:?} kept", result.params),
+        }
+    });
+    params.save(PARAMS_FILE)?;
+    println!("Saved {params:?} to {PARAMS_FILE}");
+    Ok(())
+}
+
+/// Generates `count` puzzles where the player to move wins in `win_in` moves, and saves
+/// them for `solve_connect4_puzzles`.
+pub fn generate_connect4_puzzles(
+    count: usize,
+    win_in: i32,
+    winning_tokens: i32,
+) -> Result<(), puzzle::Error> {
+    println!("Generating {count} puzzles, win in {win_in} moves");
+    let mut solver = Solver::default();
+    let mut rng = Rng::new(rng::clock_seed());
+    let mut puzzles = vec![];
+    while puzzles.len() < count {
+        let Some(puzzle) =
+            Puzzle::generate::<7, 6>(&mut solver, winning_tokens, win_in, &mut rng, PUZZLE_GAMES)
+        else {
+            println!("No puzzle found after {PUZZLE_GAMES} games");
+            break;
+        };
+        println!("Puzzle {}: {puzzle}", puzzles.len() + 1);
+        puzzles.push(puzzle);
+    }
+    puzzle::save_puzzles(PUZZLES_FILE, &puzzles)?;
+    println!("Saved {} puzzles to {PUZZLES_FILE}", puzzles.len());
+    Ok(())
+}
+
+/// Plays the saved puzzles: the human finds the winning moves, and the computer defends.
+pub fn solve_connect4_puzzles(winning_tokens: i32) -> Result<(), puzzle::Error> {
+    let puzzles = puzzle::load_puzzles(PUZZLES_FILE)?;
+    println!("Loaded {} puzzles from {PUZZLES_FILE}", puzzles.len());
+    let mut solver = Solver::default();
+    let mut lines = std::io::stdin().lines();
+    for (i, puzzle) in puzzles.iter().enumerate() {
+        let Some(mut board) = puzzle.board::<7, 6>(winning_tokens) else {
+            println!("Puzzle {}: invalid moves {puzzle}", i + 1);
+            continue;
+        };
+        let me = puzzle.to_move();
+        let mut win_in = puzzle.win_in;
+        println!("Puzzle {}:", i + 1);
+        println!("{}", board.view_as_xo());
+        loop {
+            println!("{me:?} to play and win in {win_in} moves, choose a column:");
+            let Some(line) = lines.next() else {
+                return Ok(());
+            };
+            let Ok(column) = line?.trim().parse::<i32>() else {
+                println!("Invalid number, try again");
+                continue;
+            };
+            let Some(verdict) = puzzle::check_move(&mut board, &mut solver, me, win_in, column - 1)
+            else {
+                println!("Please choose a different column");
+                continue;
+            };
+            match verdict {
+                Verdict::Solved => {
+                    board.play_column(me, column - 1);
+                    println!("{}", board.view_as_xo());
+                    println!("Solved!");
+                    break;
+                }
+                Verdict::Correct => {
+                    board.play_column(me, column - 1);
+                    let Some(defence) = puzzle::best_defence(&mut board, &mut solver, me) else {
+                        break;
+                    };
+                    board.play_column(me.other(), defence);
+                    println!("Correct, {:?} plays {}", me.other(), defence + 1);
+                    println!("{}", board.view_as_xo());
+                    win_in -= 1;
+                }
+                Verdict::Wrong { defence } => match defence {
+                    Some(defence) => println!(
+                        "Wrong, {:?} escapes by playing {}, try again",
+                        me.other(),
+                        defence + 1
+                    ),
+                    None => println!("Wrong, try again"),
+                },
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Plays `games` games of the computer against itself, `depth` moves ahead with
+/// `randomness` percent of random moves, and saves their positions as a table.
+pub fn export_connect4_games(
+    games: usize,
+    depth: i32,
+    randomness: u32,
+    winning_tokens: i32,
+) -> Result<(), Error> {
+    println!("Playing {games} games searched {depth} moves ahead, {randomness}% random moves");
+    let mut self_play = SelfPlay::new(winning_tokens, depth, randomness, rng::clock_seed());
+    let writer = std::io::BufWriter::new(std::fs::File::create(GAMES_FILE)?);
+    let positions = self_play.export::<7, 6>(games, writer, |played| {
+        if played % 100 == 0 {
+            println!("{played} games played");
+        }
+    })?;
+    println!("Saved {positions} positions to {GAMES_FILE}");
+    Ok(())
+}
+
+/// Trains the evaluation network for `epochs` epochs on the games saved by
+/// `export_connect4_games`, saves it, and plays it against the evaluation of the lines,
+/// `depth` moves ahead.
+///
+/// The training starts from the saved network, if any.
+pub fn train_connect4_evaluator(
+    epochs: usize,
+    depth: i32,
+    winning_tokens: i32,
+) -> Result<(), mlp::Error> {
+    let samples = mlp::read_samples(
+        std::io::BufReader::new(std::fs::File::open(GAMES_FILE)?),
+        winning_tokens,
+    )?;
+    let mut mlp = match Mlp::load(MLP_FILE) {
+        Err(mlp::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+            Mlp::new(winning_tokens, MLP_HIDDEN, 0)
+        }
+        mlp => mlp?,
+    };
+    println!("Training on {} positions of {GAMES_FILE}", samples.len());
+    let mut epoch = 0;
+    mlp.train(&samples, epochs, LEARNING_RATE, 0, |error| {
+        epoch += 1;
+        println!("Epoch {epoch}: mean squared error {error:.4}");
+    });
+    mlp.save(MLP_FILE)?;
+    println!("Saved the network to {MLP_FILE}");
+
+    let evaluator = std::sync::Arc::new(mlp);
+    let score = Tuner::new(winning_tokens, depth)
+        .play_engines::<7, 6>(|engine| engine.evaluator = Some(evaluator.clone()), |_| ());
+    println!(
+        "The network won {:.1}% of the points against the evaluation of the lines",
+        score * 100.
+    );
+    Ok(())
+}
+
+/// Plays each level against the previous one, and saves the share of the points it won.
+pub fn calibrate_connect4_levels(winning_tokens: i32) -> Result<(), level::Error> {
+    println!("Calibrating the levels, each playing the previous one");
+    let calibration = level::calibrate::<7, 6>(winning_tokens, |calibration| {
+        println!(
+            "Level '{}' won {:.1}% of the points",
+            calibration.level,
+            calibration.score * 100.
+        );
+    });
+    level::save_calibration(LEVELS_FILE, &calibration)?;
+    println!("Saved the calibration to {LEVELS_FILE}");
+    Ok(())
+}
+
+/// Shows and analyzes the position written as moves like `4453321`, or as a grid like
+/// `7/7/7/7/3Y3/2RR3 Y`.
+pub fn analyze_connect4_position(text: &str, winning_tokens: i32) -> Result<(), notation::Error> {
+    let (mut board, to_move) = if text.contains('/') {
+        super::board::Connect4::<7, 6>::from_grid(text, winning_tokens)?
+    } else {
+        super::board::Connect4::<7, 6>::from_moves(text, winning_tokens)?
+    };
+    println!("Grid: {}", board.to_grid(to_move));
+    println!("{}", board.view_as_xo());
+    let moves = board.analyze(&mut Engine::default(), to_move, ANALYSIS_DEPTH);
+    println!("{to_move:?} to play:");
+    println!("{}", board.view_analysis(&moves));
+    Ok(())
+}
+
+/// Drives the Connect4 AI from another program on stdin and stdout, with the UCI-like
+/// protocol of `protocol::Session`, once the program sent `uci`.
+pub fn run_connect4_protocol() -> Result<(), Error> {
+    let input = std::io::Cursor::new("uci\n").chain(std::io::stdin().lock());
+    protocol::run(input, std::io::stdout())?;
     Ok(())
 }
 
@@ -81,7 +590,4 @@ pub fn play_connect4(difficulty: i32, winning_tokens: i32) -> Result<(), Error>
 pub enum Error {
     #[error("I/O error")]
     IoError(#[from] std::io::Error),
-
-    #[error("End of the game")]
-    EOG,
 }
@@ -0,0 +1,207 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use super::bitboard::BitBoard;
+use super::board::Connect4;
+use super::engine::Engine;
+use super::personality::Personality;
+use super::position::Position;
+use super::stats::SearchStats;
+use super::strategy::search_bitboard;
+use super::strategy::Move;
+use super::symbol::Symbol;
+
+/// Search of the position expected after the next move of the human, running in a
+/// background thread while the human thinks.
+///
+/// The search is deepened one move at a time until it is stopped, sharing the
+/// transposition table of the engine that started it, so that the search of the actual
+/// position picks up its results.
+pub struct Ponder {
+    /// Column expected from the human.
+    pub column: i32,
+
+    abort: Arc<AtomicBool>,
+
+    /// Results of the completed searches, one move deeper each time.
+    results: mpsc::Receiver<(i32, Move)>,
+
+    thread: Option<JoinHandle<SearchStats>>,
+}
+
+/// Result of a search that ran while the human thought, on the position they played.
+#[derive(Debug)]
+pub struct Pondered {
+    /// Depth of the last completed search.
+    pub depth: i32,
+
+    pub next_move: Move,
+
+    /// Statistics of all the searches.
+    pub stats: SearchStats,
+}
+
+impl Ponder {
+    /// Starts searching the move of `symbol` once its opponent played `column` on `board`,
+    /// with a helper of `engine`.
+    ///
+    /// Returns `None` if the board doesn't fit in a bitboard, or if `column` can't be played
+    /// or ends the game. Also returns `None` if `engine` doesn't play the best move: the
+    /// table filled while the human thinks would change the scores its random choices are
+    /// made from, depending on how long they thought, and a seed would not replay a game.
+    pub fn start<const WIDTH: usize, const HEIGHT: usize>(
+        board: &Connect4<WIDTH, HEIGHT>,
+        engine: &Engine,
+        symbol: Symbol,
+        column: i32,
+    ) -> Option<Self> {
+        if engine.personality != Personality::Best {
+            return None;
+        }
+        let mut position = BitBoard::from_board(board, symbol.other())?;
+        if position
+            .play_column(symbol.other(), column)?
+            .outcome
+            .is_over()
+        {
+            return None;
+        }
+        let abort = Arc::new(AtomicBool::new(false));
+        let mut helper = engine.helper(abort.clone());
+        helper.deadline = None;
+        let (sender, results) = mpsc::channel();
+        let empty_cells = (WIDTH * HEIGHT) as i32 - position.mask.count_ones() as i32;
+        let thread = std::thread::spawn(move || {
+            for depth in 0..empty_cells {
+                let next_move = search_bitboard(position.clone(), &mut helper, symbol, depth);
+                if helper.stopped {
+                    break;
+                }
+                let Some(next_move) = next_move else {
+                    break;
+                };
+                if sender.send((depth, next_move)).is_err() {
+                    break;
+                }
+            }
+            helper.stats
+        });
+        Some(Self {
+            column,
+            abort,
+            results,
+            thread: Some(thread),
+        })
+    }
+
+    /// Stops the search, and returns its last result if the human played the expected
+    /// `column`. Otherwise the search is discarded.
+    pub fn stop(mut self, column: i32) -> Option<Pondered> {
+        self.abort.store(true, Ordering::Relaxed);
+        let stats = self.thread.take()?.join().ok()?;
+        if column != self.column {
+            return None;
+        }
+        let (depth, next_move) = self.results.try_iter().last()?;
+        Some(Pondered {
+            depth,
+            next_move,
+            stats,
+        })
+    }
+}
+
+impl Drop for Ponder {
+    fn drop(&mut self) {
+        self.abort.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connect4::zobrist;
+    use crate::shared::rng::Rng;
+
+    use super::*;
+
+    #[test]
+    fn hit() {
+        let mut board = Connect4::<7, 6>::new(4);
+        board.play_column(Symbol::Red, 3);
+        let engine = Engine::new(1 << 20);
+        let ponder = Ponder::start(&board, &engine, Symbol::Red, 3).unwrap();
+        // Once the search 2 moves ahead is stored, the one a move ahead was sent.
+        let play_column = board.play_column(Symbol::Yellow, 3).unwrap();
+        let side_key = zobrist::side_key(Symbol::Red);
+        let hash = (board.hash() ^ side_key).min(board.mirror_hash() ^ side_key);
+        play_column.undo(&mut board);
+        while engine.table.get(hash).is_none_or(|entry| entry.depth < 2) {
+            std::thread::yield_now();
+        }
+        let pondered = ponder.stop(3).unwrap();
+        assert!(pondered.depth >= 1);
+        assert!(pondered.stats.nodes > 0);
+        assert_eq!(pondered.next_move.column, pondered.next_move.pv[0]);
+
+        // The search of the position played finds the pondered search in the table.
+        board.play_column(Symbol::Yellow, 3);
+        let mut engine = engine;
+        let next_move = board
+            .next_move_with(&mut engine, Symbol::Red, pondered.depth)
+            .unwrap();
+        assert_eq!(
+            (pondered.next_move.column, pondered.next_move.score),
+            (next_move.column, next_move.score)
+        );
+        assert_eq!(1, engine.stats.nodes);
+    }
+
+    #[test]
+    fn miss() {
+        let mut board = Connect4::<7, 6>::new(4);
+        board.play_column(Symbol::Red, 3);
+        let engine = Engine::new(1 << 20);
+        let ponder = Ponder::start(&board, &engine, Symbol::Red, 3).unwrap();
+        assert_eq!(3, ponder.column);
+        assert!(ponder.stop(2).is_none());
+
+        // Dropped without being stopped.
+        Ponder::start(&board, &engine, Symbol::Red, 3).unwrap();
+
+        let mut board = Connect4::<5, 4>::new(3);
+        board.play_column(Symbol::Red, 0);
+        board.play_column(Symbol::Red, 1);
+        assert!(Ponder::start(&board, &engine, Symbol::Yellow, 2).is_none());
+    }
+
+    #[test]
+    fn same_seed_same_game() {
+        let play = || {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut engine = Engine::new(1 << 16);
+            engine.personality = Personality::Blunder { rate: 0.5 };
+            engine.rng = Rng::new(7);
+            let mut columns = vec![];
+            for column in [3, 2, 4, 3, 1, 5] {
+                board.play_column(Symbol::Red, column);
+                let next_move = board
+                    .next_move_with(&mut engine, Symbol::Yellow, 4)
+                    .unwrap();
+                board.play_column(Symbol::Yellow, next_move.column);
+                columns.push(next_move.column);
+                if let Some(&expected) = next_move.pv.get(1) {
+                    let ponder = Ponder::start(&board, &engine, Symbol::Yellow, expected);
+                    assert!(ponder.is_none());
+                }
+            }
+            columns
+        };
+        assert_eq!(play(), play());
+    }
+}
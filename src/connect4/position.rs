@@ -0,0 +1,41 @@
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::board::board_size::BoardSize;
+
+use super::cell::Connect4Cell;
+use super::game::PlayColumn;
+use super::symbol::Symbol;
+
+/// A Connect4 board the strategy can play on.
+pub trait Position: BoardSize + BoardGet<Value = Connect4Cell> {
+    /// Drops a token of `symbol` in `column`, or returns `None` if the column is full.
+    fn play_column(&mut self, symbol: Symbol, column: i32) -> Option<PlayColumn>;
+
+    /// Removes the token added by `play_column`.
+    fn undo_column(&mut self, play_column: &PlayColumn);
+
+    /// Zobrist hash of the board, the same for all the implementations.
+    fn hash(&self) -> u64;
+
+    /// Zobrist hash of the mirror image of the board, the first column becoming the last
+    /// one.
+    fn mirror_hash(&self) -> u64;
+
+    /// Whether no line can be filled anymore, each of them holding tokens of both players,
+    /// so that the game can only end in a draw.
+    fn is_dead(&self) -> bool;
+
+    /// Whether the board is its own mirror image, so that a column and its mirror image are
+    /// equally good.
+    fn is_symmetric(&self) -> bool {
+        self.hash() == self.mirror_hash()
+    }
+}
+
+/// `column` of a board of `width` columns, or its mirror image when `mirrored`.
+pub(super) fn mirror_column(column: i32, width: usize, mirrored: bool) -> i32 {
+    if mirrored {
+        width as i32 - 1 - column
+    } else {
+        column
+    }
+}
@@ -0,0 +1,469 @@
+use std::io::BufRead;
+use std::io::Write;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+use super::board::Connect4;
+use super::engine::Engine;
+use super::notation;
+
+/// Sizes of the boards the search is compiled for, as `(width, height)`.
+const SIZES: [(usize, usize); 5] = [(4, 4), (5, 4), (6, 5), (7, 6), (8, 7)];
+
+const DEFAULT_SIZE: (usize, usize) = (7, 6);
+const DEFAULT_WINNING_TOKENS: i32 = 4;
+
+/// Bounds of the `winning_tokens` option.
+const MIN_WINNING_TOKENS: i32 = 3;
+const MAX_WINNING_TOKENS: i32 = 8;
+
+/// Line protocol driving the Connect4 AI from another program, modelled on UCI.
+///
+/// The commands are:
+/// - `uci`: lists the options, then prints `uciok`.
+/// - `isready`: prints `readyok`, even while searching.
+/// - `setoption name size value <width>x<height>`, among the sizes listed by `uci`.
+/// - `setoption name winning_tokens value <n>`.
+/// - `ucinewgame`: forgets the positions searched.
+/// - `position startpos [moves <column>...]`: the moves played since the empty board,
+///   1-based columns, Red first, with or without spaces between them like `4453321`.
+/// - `position fen <grid>`: the position written like `Connect4::to_grid`.
+/// - `go [depth <n>] [movetime <milliseconds>] [infinite]`: searches the position in the
+///   background, deepening the search one move at a time until a limit is reached. Each
+///   completed depth prints `info depth <n> score <score> nodes <n> time <milliseconds>
+///   pv <column>...`, and the search ends with `bestmove <column>`, or `bestmove none` if
+///   the game is over.
+/// - `stop`: stops the search, which prints its `bestmove`.
+/// - `quit`: stops the search and exits.
+///
+/// Any other command waits for the search to end. Errors are reported as
+/// `info string <error>`.
+pub struct Session<W> {
+    width: usize,
+    height: usize,
+    winning_tokens: i32,
+
+    /// Columns played since the empty board.
+    moves: Vec<i32>,
+
+    engine: Engine,
+
+    /// Shared with the search thread, which prints its `info` and `bestmove` lines.
+    output: Arc<Mutex<W>>,
+
+    search: Option<Search>,
+}
+
+/// Search running in the background.
+struct Search {
+    abort: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// Limits of a search, from the arguments of `go`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Limits {
+    depth: Option<i32>,
+    movetime: Option<Duration>,
+}
+
+/// Runs the protocol on the commands of `input` until `quit` or the end of the input, and
+/// returns `output` once the last search ended.
+pub fn run<W: Write + Send + 'static>(input: impl BufRead, output: W) -> std::io::Result<W> {
+    let mut session = Session::new(output);
+    for line in input.lines() {
+        if !session.execute(line?.trim())? {
+            break;
+        }
+    }
+    session.into_output()
+}
+
+impl<W: Write + Send + 'static> Session<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            width: DEFAULT_SIZE.0,
+            height: DEFAULT_SIZE.1,
+            winning_tokens: DEFAULT_WINNING_TOKENS,
+            moves: vec![],
+            engine: Engine::default(),
+            output: Arc::new(Mutex::new(output)),
+            search: None,
+        }
+    }
+
+    /// Executes a command, and tells whether to read the next one.
+    pub fn execute(&mut self, line: &str) -> std::io::Result<bool> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(true);
+        };
+        let words: Vec<&str> = words.collect();
+        match command {
+            "isready" => self.send("readyok")?,
+            "stop" => self.stop(),
+            "quit" => {
+                self.stop();
+                return Ok(false);
+            }
+            command => {
+                self.wait();
+                let result = match command {
+                    "uci" => self.uci(),
+                    "setoption" => self.set_option(&words),
+                    "ucinewgame" => {
+                        self.engine = Engine::default();
+                        Ok(())
+                    }
+                    "position" => self.position(&words),
+                    "go" => self.go(&words),
+                    _ => Err(Error::UnknownCommand(line.to_owned())),
+                };
+                match result {
+                    Ok(()) => (),
+                    Err(Error::Io(err)) => return Err(err),
+                    Err(err) => self.send(&format!("info string {err}"))?,
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Waits for the search to end, and returns the output.
+    pub fn into_output(mut self) -> std::io::Result<W> {
+        self.wait();
+        let output = self.output.clone();
+        drop(self);
+        Ok(Arc::into_inner(output)
+            .and_then(|output| output.into_inner().ok())
+            .expect("the search thread ended"))
+    }
+
+    fn send(&self, line: &str) -> std::io::Result<()> {
+        let mut output = self.output.lock().unwrap();
+        writeln!(output, "{line}")?;
+        output.flush()
+    }
+
+    fn uci(&self) -> Result<(), Error> {
+        self.send("id name connect4")?;
+        let sizes: Vec<String> = SIZES
+            .iter()
+            .map(|(width, height)| format!("var {width}x{height}"))
+            .collect();
+        self.send(&format!(
+            "option name size type combo default {}x{} {}",
+            DEFAULT_SIZE.0,
+            DEFAULT_SIZE.1,
+            sizes.join(" ")
+        ))?;
+        self.send(&format!(
+            "option name winning_tokens type spin default {DEFAULT_WINNING_TOKENS} min {MIN_WINNING_TOKENS} max {MAX_WINNING_TOKENS}"
+        ))?;
+        self.send("uciok")?;
+        Ok(())
+    }
+
+    /// Sets the size of the board or the number of winning tokens, and goes back to the
+    /// empty board.
+    fn set_option(&mut self, words: &[&str]) -> Result<(), Error> {
+        let invalid = || Error::InvalidOption(words.join(" "));
+        let ["name", name, "value", value] = words else {
+            return Err(invalid());
+        };
+        match *name {
+            "size" => {
+                let (width, height) = value
+                    .split_once('x')
+                    .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                    .filter(|size| SIZES.contains(size))
+                    .ok_or_else(invalid)?;
+                self.width = width;
+                self.height = height;
+            }
+            "winning_tokens" => {
+                self.winning_tokens = value
+                    .parse()
+                    .ok()
+                    .filter(|winning_tokens| {
+                        (MIN_WINNING_TOKENS..=MAX_WINNING_TOKENS).contains(winning_tokens)
+                    })
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+        self.moves.clear();
+        Ok(())
+    }
+
+    fn position(&mut self, words: &[&str]) -> Result<(), Error> {
+        self.moves = match (self.width, self.height) {
+            (4, 4) => position_moves::<4, 4>(self.winning_tokens, words),
+            (5, 4) => position_moves::<5, 4>(self.winning_tokens, words),
+            (6, 5) => position_moves::<6, 5>(self.winning_tokens, words),
+            (7, 6) => position_moves::<7, 6>(self.winning_tokens, words),
+            (8, 7) => position_moves::<8, 7>(self.winning_tokens, words),
+            _ => unreachable!("sizes are checked by set_option"),
+        }?;
+        Ok(())
+    }
+
+    fn go(&mut self, words: &[&str]) -> Result<(), Error> {
+        let mut limits = Limits::default();
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            let mut value = || {
+                words
+                    .next()
+                    .and_then(|value| value.parse::<u64>().ok())
+                    .ok_or_else(|| Error::InvalidLimit(word.to_string()))
+            };
+            match *word {
+                "depth" => {
+                    let depth = value()?;
+                    if depth == 0 {
+                        return Err(Error::InvalidLimit(word.to_string()));
+                    }
+                    limits.depth = Some(depth.min(i32::MAX as u64) as i32);
+                }
+                "movetime" => limits.movetime = Some(Duration::from_millis(value()?)),
+                "infinite" => (),
+                _ => return Err(Error::InvalidLimit(word.to_string())),
+            }
+        }
+        self.search = Some(match (self.width, self.height) {
+            (4, 4) => self.start::<4, 4>(limits),
+            (5, 4) => self.start::<5, 4>(limits),
+            (6, 5) => self.start::<6, 5>(limits),
+            (7, 6) => self.start::<7, 6>(limits),
+            (8, 7) => self.start::<8, 7>(limits),
+            _ => unreachable!("sizes are checked by set_option"),
+        }?);
+        Ok(())
+    }
+
+    /// Starts searching the position in the background, with a helper of the engine so
+    /// that the next searches find its results in the transposition table.
+    fn start<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        limits: Limits,
+    ) -> Result<Search, Error> {
+        let (mut board, outcome, symbol) =
+            Connect4::<WIDTH, HEIGHT>::replay(self.winning_tokens, &self.moves)?;
+        let abort = Arc::new(AtomicBool::new(false));
+        let mut helper = self.engine.helper(abort.clone());
+        let output = self.output.clone();
+        let empty_cells = (WIDTH * HEIGHT - self.moves.len()) as i32;
+        let max_depth = limits
+            .depth
+            .map_or(empty_cells, |depth| depth.min(empty_cells));
+        let thread = std::thread::spawn(move || {
+            let start = Instant::now();
+            let deadline = limits.movetime.map(|movetime| start + movetime);
+            let mut best_move = None;
+            for depth in 0..if outcome.is_over() { 0 } else { max_depth } {
+                // The search at depth 0 always completes.
+                if depth > 0 {
+                    if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                        break;
+                    }
+                    helper.deadline = deadline;
+                }
+                let next_move = board.search(&mut helper, symbol, depth);
+                if helper.stopped {
+                    break;
+                }
+                let Some(next_move) = next_move else {
+                    break;
+                };
+                let pv: Vec<String> = next_move
+                    .pv
+                    .iter()
+                    .map(|column| (column + 1).to_string())
+                    .collect();
+                let mut output = output.lock().unwrap();
+                let _ = writeln!(
+                    output,
+                    "info depth {} score {} nodes {} time {} pv {}",
+                    depth + 1,
+                    next_move.score,
+                    helper.stats.nodes,
+                    start.elapsed().as_millis(),
+                    pv.join(" ")
+                );
+                let _ = output.flush();
+                best_move = Some(next_move.column);
+            }
+            let mut output = output.lock().unwrap();
+            let _ = match best_move {
+                Some(column) => writeln!(output, "bestmove {}", column + 1),
+                None => writeln!(output, "bestmove none"),
+            };
+            let _ = output.flush();
+        });
+        Ok(Search { abort, thread })
+    }
+
+    /// Stops the search, if any, and waits for its `bestmove`.
+    fn stop(&mut self) {
+        if let Some(search) = &self.search {
+            search.abort.store(true, Ordering::Relaxed);
+        }
+        self.wait();
+    }
+
+    /// Waits for the search to end, if any.
+    fn wait(&mut self) {
+        if let Some(search) = self.search.take() {
+            let _ = search.thread.join();
+        }
+    }
+}
+
+impl<W> Drop for Session<W> {
+    fn drop(&mut self) {
+        if let Some(search) = self.search.take() {
+            search.abort.store(true, Ordering::Relaxed);
+            let _ = search.thread.join();
+        }
+    }
+}
+
+/// Columns played since the empty board to reach the position of the arguments of
+/// `position`, checked on the board.
+fn position_moves<const WIDTH: usize, const HEIGHT: usize>(
+    winning_tokens: i32,
+    words: &[&str],
+) -> Result<Vec<i32>, Error> {
+    let moves = match words {
+        ["startpos"] => vec![],
+        ["startpos", "moves", moves @ ..] => notation::parse_moves(&moves.concat())?,
+        ["fen", grid @ ..] => {
+            notation::grid_moves::<WIDTH, HEIGHT>(&grid.join(" "), winning_tokens)?
+        }
+        _ => return Err(Error::InvalidPosition(words.join(" "))),
+    };
+    Connect4::<WIDTH, HEIGHT>::replay(winning_tokens, &moves)?;
+    Ok(moves)
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Unknown command: {}", .0)]
+    UnknownCommand(String),
+
+    #[error("Invalid option: {}", .0)]
+    InvalidOption(String),
+
+    #[error("Invalid position: {}", .0)]
+    InvalidPosition(String),
+
+    #[error("Invalid position: {}", .0)]
+    Notation(#[from] notation::Error),
+
+    #[error("Invalid limit: {}", .0)]
+    InvalidLimit(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of the protocol for the commands of `input`, `info depth` lines reduced to
+    /// their depth and principal variation, whose time varies.
+    fn transcript(input: &str) -> Vec<String> {
+        let output = run(input.as_bytes(), vec![]).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| match line.strip_prefix("info depth ") {
+                Some(info) => {
+                    let (depth, _) = info.split_once(' ').unwrap();
+                    let (_, pv) = info.split_once(" pv ").unwrap();
+                    format!("info depth {depth} pv {pv}")
+                }
+                None => line.to_owned(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn session() {
+        let input = "
+            uci
+            setoption name size value 5x4
+            setoption name winning_tokens value 3
+            isready
+            position startpos moves 1 2 1 2
+            go depth 2
+            position startpos moves 1 1 1 1 1
+            position startpos moves 6
+            position fen 5/5/1R3/5 Y
+            setoption name size value 9x9
+            go depth 0
+            go nodes 5
+            solve
+            position startpos moves 12 121
+            go
+            position fen 5/5/5/RY3 R
+            go depth 1
+            quit
+            go depth 1
+        ";
+        assert_eq!(
+            vec![
+                "id name connect4",
+                "option name size type combo default 7x6 var 4x4 var 5x4 var 6x5 var 7x6 var 8x7",
+                "option name winning_tokens type spin default 4 min 3 max 8",
+                "uciok",
+                "readyok",
+                "info depth 1 pv 1",
+                "info depth 2 pv 1",
+                "bestmove 1",
+                "info string Invalid position: Move 5: column 1 is full",
+                "info string Invalid position: Move 1: there is no column 6",
+                "info string Invalid position: Row 3, column 2: the disc floats above an empty cell",
+                "info string Invalid option: name size value 9x9",
+                "info string Invalid limit: depth",
+                "info string Invalid limit: nodes",
+                "info string Unknown command: solve",
+                "bestmove none",
+                "info depth 1 pv 2",
+                "bestmove 2",
+            ],
+            transcript(input)
+        );
+    }
+
+    #[test]
+    fn stop() {
+        let mut session = Session::new(vec![]);
+        session.execute("position startpos moves 4").unwrap();
+        session.execute("go infinite").unwrap();
+        std::thread::sleep(Duration::from_millis(100));
+        session.execute("stop").unwrap();
+        session.execute("isready").unwrap();
+        let output = String::from_utf8(session.into_output().unwrap()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("info depth 1 score "), "{output}");
+        assert!(lines[lines.len() - 2].starts_with("bestmove "), "{output}");
+        assert_eq!("readyok", lines[lines.len() - 1]);
+    }
+
+    #[test]
+    fn movetime() {
+        let start = Instant::now();
+        let output = run("go movetime 50".as_bytes(), vec![]).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().last().unwrap().starts_with("bestmove "));
+    }
+}
@@ -0,0 +1,366 @@
+use std::fmt::Display;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+use crate::shared::rng::Rng;
+
+use super::board::Connect4;
+use super::notation;
+use super::position::Position;
+use super::solver::Solution;
+use super::solver::Solver;
+use super::symbol::Symbol;
+
+/// How many moves before the end of a random game the positions are tried as puzzles,
+/// besides the moves of the puzzle. Earlier positions take too long to solve.
+const EXTRA_PLIES: usize = 8;
+
+/// Position where the player to move has a forced win in exactly `win_in` moves, and no
+/// faster one.
+///
+/// A puzzle is written on a line as `win_in` followed by the columns played from the empty
+/// board, Red first, numbered from 1 and without separators, like `2 4453`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Puzzle {
+    pub win_in: i32,
+    pub moves: Vec<i32>,
+}
+
+/// Verdict of `check_move` on a move of a puzzle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    /// The move wins the game.
+    Solved,
+
+    /// The move keeps a forced win in one move less, whatever the defence.
+    Correct,
+
+    /// The move doesn't win in time: the opponent escapes with `defence`, or with any move
+    /// when `None`.
+    Wrong { defence: Option<i32> },
+}
+
+impl Puzzle {
+    /// Generates a puzzle from random games of `winning_tokens` played with `rng`, trying
+    /// the last positions of each game, up to `games` games.
+    ///
+    /// Returns `None` if none of the games has such a position, if the board doesn't fit in
+    /// a bitboard, or if it has fewer cells than the moves of a win in `win_in` moves.
+    pub fn generate<const WIDTH: usize, const HEIGHT: usize>(
+        solver: &mut Solver,
+        winning_tokens: i32,
+        win_in: i32,
+        rng: &mut Rng,
+        games: usize,
+    ) -> Option<Self> {
+        if win_in < 1 || 2 * win_in as usize - 1 > WIDTH * HEIGHT {
+            return None;
+        }
+        for _ in 0..games {
+            let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+            let mut played = vec![];
+            let mut symbol = Symbol::Red;
+            loop {
+                let columns: Vec<i32> = (0..WIDTH as i32)
+                    .filter(|column| board.get(&Point::new(*column, 0)).symbol == Symbol::Empty)
+                    .collect();
+                let Some(&column) = rng.choose(&columns) else {
+                    break;
+                };
+                let play_column = board.play_column(symbol, column).unwrap();
+                let over = play_column.outcome.is_over();
+                played.push(play_column);
+                if over {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+
+            // The side to move wins with the last token at the earliest.
+            let plies = 2 * win_in as usize - 1;
+            for _ in 0..(plies + EXTRA_PLIES).min(played.len()) {
+                let play_column = played.pop().unwrap();
+                play_column.undo(&mut board);
+                let symbol = to_move(played.len());
+                if solver.solve(&board, symbol)?
+                    == (Solution::Win {
+                        plies: plies as i32,
+                    })
+                {
+                    return Some(Self {
+                        win_in,
+                        moves: played
+                            .iter()
+                            .map(|play_column| play_column.position.x)
+                            .collect(),
+                    });
+                }
+            }
+        }
+        None
+    }
+
+    /// The player to move.
+    pub fn to_move(&self) -> Symbol {
+        to_move(self.moves.len())
+    }
+
+    /// The board of the puzzle, or `None` if its moves can't be played.
+    pub fn board<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        winning_tokens: i32,
+    ) -> Option<Connect4<WIDTH, HEIGHT>> {
+        let mut board = Connect4::new(winning_tokens);
+        for (i, column) in self.moves.iter().enumerate() {
+            if board.play_column(to_move(i), *column)?.outcome.is_over() {
+                return None;
+            }
+        }
+        Some(board)
+    }
+}
+
+/// Checks `column`, played by `symbol` on `board` where it has a forced win in `win_in`
+/// moves, against all the defences of the opponent.
+///
+/// Returns `None` if `column` can't be played, or if the board doesn't fit in a bitboard.
+pub fn check_move<const WIDTH: usize, const HEIGHT: usize>(
+    board: &mut Connect4<WIDTH, HEIGHT>,
+    solver: &mut Solver,
+    symbol: Symbol,
+    win_in: i32,
+    column: i32,
+) -> Option<Verdict> {
+    let play_column = board.play_column(symbol, column)?;
+    let verdict = match play_column.outcome {
+        GameOutcome::Win { .. } => Some(Verdict::Solved),
+        GameOutcome::Ongoing if win_in > 1 => {
+            let mut verdict = Some(Verdict::Correct);
+            for defence in 0..WIDTH as i32 {
+                let Some(reply) = board.play_column(symbol.other(), defence) else {
+                    continue;
+                };
+                let holds = match reply.outcome {
+                    GameOutcome::Ongoing => match solver.solve(board, symbol) {
+                        Some(Solution::Win { plies }) => plies < 2 * (win_in - 1),
+                        Some(_) => false,
+                        None => {
+                            verdict = None;
+                            true
+                        }
+                    },
+                    _ => false,
+                };
+                reply.undo(board);
+                if !holds {
+                    verdict = Some(Verdict::Wrong {
+                        defence: Some(defence),
+                    });
+                    break;
+                }
+            }
+            verdict
+        }
+        _ => Some(Verdict::Wrong { defence: None }),
+    };
+    play_column.undo(board);
+    verdict
+}
+
+/// The defence of the opponent of `symbol` on `board` that delays the win of `symbol` the
+/// most, or escapes it.
+pub fn best_defence<const WIDTH: usize, const HEIGHT: usize>(
+    board: &mut Connect4<WIDTH, HEIGHT>,
+    solver: &mut Solver,
+    symbol: Symbol,
+) -> Option<i32> {
+    let mut best: Option<(i32, i32)> = None;
+    for defence in 0..WIDTH as i32 {
+        let Some(reply) = board.play_column(symbol.other(), defence) else {
+            continue;
+        };
+        // How long `symbol` takes to win, escaping the loss counting as forever.
+        let delay = match reply.outcome {
+            GameOutcome::Ongoing => match solver.solve(board, symbol) {
+                Some(Solution::Win { plies }) => plies,
+                _ => i32::MAX,
+            },
+            GameOutcome::Win { .. } => i32::MAX,
+            GameOutcome::Draw => i32::MAX - 1,
+        };
+        reply.undo(board);
+        if best.is_none_or(|(_, best_delay)| best_delay < delay) {
+            best = Some((defence, delay));
+        }
+    }
+    best.map(|(defence, _)| defence)
+}
+
+fn to_move(moves: usize) -> Symbol {
+    if moves.is_multiple_of(2) {
+        Symbol::Red
+    } else {
+        Symbol::Yellow
+    }
+}
+
+impl Display for Puzzle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.win_in, notation::write_moves(&self.moves))
+    }
+}
+
+impl FromStr for Puzzle {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidPuzzle(line.to_owned());
+        let (win_in, moves) = line.trim().split_once(' ').ok_or_else(invalid)?;
+        let win_in = win_in
+            .parse::<i32>()
+            .ok()
+            .filter(|win_in| *win_in > 0)
+            .ok_or_else(invalid)?;
+        let moves = notation::parse_moves(moves).map_err(|_| invalid())?;
+        Ok(Self { win_in, moves })
+    }
+}
+
+pub fn load_puzzles(path: impl AsRef<Path>) -> Result<Vec<Puzzle>, Error> {
+    read_puzzles(std::io::BufReader::new(std::fs::File::open(path)?))
+}
+
+pub fn save_puzzles(path: impl AsRef<Path>, puzzles: &[Puzzle]) -> Result<(), Error> {
+    let mut writer = std::io::BufWriter::new(std::fs::File::create(path)?);
+    write_puzzles(&mut writer, puzzles)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads puzzles, one per line, skipping the empty lines and the comments starting with
+/// `#`.
+pub fn read_puzzles(reader: impl BufRead) -> Result<Vec<Puzzle>, Error> {
+    let mut puzzles = vec![];
+    for line in reader.lines() {
+        let line = line?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            puzzles.push(line.parse()?);
+        }
+    }
+    Ok(puzzles)
+}
+
+pub fn write_puzzles(mut writer: impl Write, puzzles: &[Puzzle]) -> Result<(), Error> {
+    for puzzle in puzzles {
+        writeln!(writer, "{puzzle}")?;
+    }
+    Ok(())
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid puzzle: {}", .0)]
+    InvalidPuzzle(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate() {
+        let mut solver = Solver::new(1 << 16);
+        let mut rng = Rng::new(0);
+        for win_in in 1..=3 {
+            let puzzle = Puzzle::generate::<5, 4>(&mut solver, 3, win_in, &mut rng, 100).unwrap();
+            assert_eq!(win_in, puzzle.win_in);
+            let board = puzzle.board::<5, 4>(3).unwrap();
+            assert_eq!(
+                Some(Solution::Win {
+                    plies: 2 * win_in - 1
+                }),
+                solver.solve(&board, puzzle.to_move())
+            );
+        }
+        assert!(Puzzle::generate::<5, 4>(&mut solver, 3, 11, &mut rng, 100).is_none());
+        assert!(Puzzle::generate::<5, 4>(&mut solver, 3, 0, &mut rng, 100).is_none());
+    }
+
+    #[test]
+    fn check_move() {
+        let mut solver = Solver::new(1 << 16);
+
+        // Red has 3 tokens in the bottom row.
+        let puzzle: Puzzle = "1 112233".parse().unwrap();
+        let mut board = puzzle.board::<7, 6>(4).unwrap();
+        let check = |board: &mut Connect4<7, 6>, solver: &mut Solver, column| {
+            super::check_move(board, solver, Symbol::Red, puzzle.win_in, column)
+        };
+        assert_eq!(Some(Verdict::Solved), check(&mut board, &mut solver, 3));
+        assert_eq!(
+            Some(Verdict::Wrong { defence: None }),
+            check(&mut board, &mut solver, 4)
+        );
+
+        // Red has 2 tokens in the middle of the bottom row.
+        let puzzle: Puzzle = "2 3344".parse().unwrap();
+        let mut board = puzzle.board::<7, 6>(4).unwrap();
+        assert_eq!(
+            Some(Solution::Win { plies: 3 }),
+            solver.solve(&board, Symbol::Red)
+        );
+        let check = |board: &mut Connect4<7, 6>, solver: &mut Solver, column| {
+            super::check_move(board, solver, Symbol::Red, puzzle.win_in, column)
+        };
+        assert_eq!(Some(Verdict::Correct), check(&mut board, &mut solver, 1));
+        assert_eq!(Some(Verdict::Correct), check(&mut board, &mut solver, 4));
+        assert!(matches!(
+            check(&mut board, &mut solver, 6),
+            Some(Verdict::Wrong { defence: Some(_) })
+        ));
+
+        board.play_column(Symbol::Red, 1);
+        let defence = best_defence(&mut board, &mut solver, Symbol::Red).unwrap();
+        assert!(defence == 0 || defence == 4);
+    }
+
+    #[test]
+    fn read_write() {
+        let puzzles = vec![
+            Puzzle {
+                win_in: 2,
+                moves: vec![3, 3, 4, 4],
+            },
+            Puzzle {
+                win_in: 5,
+                moves: vec![0, 6, 2],
+            },
+        ];
+        let mut bytes = vec![];
+        write_puzzles(&mut bytes, &puzzles).unwrap();
+        assert_eq!("2 4455\n5 173\n", String::from_utf8_lossy(&bytes));
+        let text = format!("# Puzzles\n\n{}", String::from_utf8_lossy(&bytes));
+        assert_eq!(puzzles, read_puzzles(text.as_bytes()).unwrap());
+
+        for line in ["2", "0 44", "2 40", "x 44"] {
+            assert!(
+                matches!(line.parse::<Puzzle>(), Err(Error::InvalidPuzzle(_))),
+                "{line}"
+            );
+        }
+        assert!("2 4444444"
+            .parse::<Puzzle>()
+            .unwrap()
+            .board::<7, 6>(4)
+            .is_none());
+    }
+}
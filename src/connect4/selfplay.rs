@@ -0,0 +1,253 @@
+use std::io::BufRead;
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
+use crate::shared::rng::Rng;
+
+use super::board::Connect4;
+use super::engine::Engine;
+use super::notation;
+use super::position::Position;
+use super::symbol::Symbol;
+
+/// Memory of the transposition table of the engine playing the games.
+const TABLE_MEMORY: usize = 1 << 20;
+
+/// Games of the engine against itself, exported as a table of positions.
+///
+/// Each line of the table is tab-separated:
+/// - the position with the player to move, written by `Connect4::to_grid` like
+///   `7/7/7/7/3Y3/2RR3 R`,
+/// - the score of the best move found by the search,
+/// - the column played, numbered from 1,
+/// - the result of the game for the player to move: 1 for a win, 0 for a draw, -1 for a loss.
+pub struct SelfPlay {
+    /// How many moves ahead the engine searches.
+    pub depth: i32,
+
+    /// Percentage of the moves played in a random column rather than the best one, so that
+    /// the games differ.
+    pub randomness: u32,
+
+    pub winning_tokens: i32,
+
+    rng: Rng,
+}
+
+/// Position of a game of `SelfPlay`, and the move played.
+#[derive(Debug)]
+pub struct Record {
+    pub position: String,
+    pub to_move: Symbol,
+    pub score: i32,
+    pub column: i32,
+    pub result: i32,
+}
+
+impl SelfPlay {
+    /// Games of boards of `winning_tokens` searched `depth` moves ahead, the random moves
+    /// generated from `seed`.
+    pub fn new(winning_tokens: i32, depth: i32, randomness: u32, seed: u64) -> Self {
+        Self {
+            depth,
+            randomness,
+            winning_tokens,
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Plays `games` games and writes their positions to `writer`, after a header line.
+    ///
+    /// `progress` is called with the number of games played after each game. Returns the
+    /// number of positions written.
+    pub fn export<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        games: usize,
+        mut writer: impl Write,
+        mut progress: impl FnMut(usize),
+    ) -> std::io::Result<usize> {
+        writeln!(writer, "position\tscore\tcolumn\tresult")?;
+        let mut engine = Engine::new(TABLE_MEMORY);
+        let mut positions = 0;
+        for game in 0..games {
+            for record in self.play_game::<WIDTH, HEIGHT>(&mut engine) {
+                writeln!(
+                    writer,
+                    "{}\t{}\t{}\t{}",
+                    record.position,
+                    record.score,
+                    record.column + 1,
+                    record.result
+                )?;
+                positions += 1;
+            }
+            progress(game + 1);
+        }
+        writer.flush()?;
+        Ok(positions)
+    }
+
+    /// Plays a game from the empty board, and returns its positions.
+    pub fn play_game<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        engine: &mut Engine,
+    ) -> Vec<Record> {
+        let mut board = Connect4::<WIDTH, HEIGHT>::new(self.winning_tokens);
+        let mut records = vec![];
+        let mut symbol = Symbol::Red;
+        let outcome = loop {
+            let Some(best_move) = board.search(engine, symbol, self.depth) else {
+                break GameOutcome::Draw;
+            };
+            let column = if self.rng.below(100) < self.randomness as usize {
+                let columns: Vec<i32> = (0..WIDTH as i32)
+                    .filter(|column| board.get(&Point::new(*column, 0)).symbol == Symbol::Empty)
+                    .collect();
+                *self.rng.choose(&columns).unwrap()
+            } else {
+                best_move.column
+            };
+            records.push(Record {
+                position: board.to_grid(symbol),
+                to_move: symbol,
+                score: best_move.score,
+                column,
+                result: 0,
+            });
+            let outcome = board.play_column(symbol, column).unwrap().outcome;
+            if outcome.is_over() {
+                break outcome;
+            }
+            symbol = symbol.other();
+        };
+        if let Some(winner) = outcome.winner() {
+            for record in &mut records {
+                record.result = if record.to_move == winner { 1 } else { -1 };
+            }
+        }
+        records
+    }
+}
+
+/// Reads the positions written by `SelfPlay::export`.
+pub fn read_records(reader: impl BufRead) -> Result<Vec<Record>, Error> {
+    let mut records = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        // Skips the header.
+        if i > 0 && !line.trim().is_empty() {
+            records.push(line.parse()?);
+        }
+    }
+    Ok(records)
+}
+
+impl Record {
+    /// The cells of the position, rows from top to bottom, or `None` if it is invalid.
+    pub fn cells(&self) -> Option<Vec<Vec<Symbol>>> {
+        notation::parse_grid(&self.position)
+            .ok()
+            .map(|(cells, _)| cells)
+    }
+}
+
+impl FromStr for Record {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidLine(line.to_owned());
+        let fields: Vec<&str> = line.trim().split('\t').collect();
+        let [position, score, column, result] = fields[..] else {
+            return Err(invalid());
+        };
+        let (_, to_move) = notation::parse_grid(position).map_err(|_| invalid())?;
+        Ok(Self {
+            position: position.to_owned(),
+            to_move,
+            score: score.parse().map_err(|_| invalid())?,
+            column: column.parse::<i32>().map_err(|_| invalid())? - 1,
+            result: result
+                .parse()
+                .ok()
+                .filter(|result: &i32| (-1..=1).contains(result))
+                .ok_or_else(invalid)?,
+        })
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("I/O error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Invalid position: {}", .0)]
+    InvalidLine(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export() {
+        let mut self_play = SelfPlay::new(3, 2, 30, 0);
+        let mut bytes = vec![];
+        let mut games = 0;
+        let positions = self_play
+            .export::<5, 4>(3, &mut bytes, |played| games = played)
+            .unwrap();
+        assert_eq!(3, games);
+
+        let text = String::from_utf8(bytes).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(Some("position\tscore\tcolumn\tresult"), lines.next());
+        let rows: Vec<Vec<&str>> = lines.map(|line| line.split('\t').collect()).collect();
+        assert_eq!(positions, rows.len());
+        assert_eq!("5/5/5/5 R", rows[0][0]);
+        for pair in rows.windows(2) {
+            let tokens = |row: &[&str]| {
+                let (cells, _) = notation::parse_grid(row[0]).unwrap();
+                cells
+                    .iter()
+                    .flatten()
+                    .filter(|cell| **cell != Symbol::Empty)
+                    .count()
+            };
+            if tokens(&pair[1]) == 0 {
+                // First position of the next game.
+                continue;
+            }
+            assert_eq!(tokens(&pair[0]) + 1, tokens(&pair[1]));
+            assert_ne!(pair[0][0].chars().last(), pair[1][0].chars().last());
+            assert_eq!(
+                -pair[0][3].parse::<i32>().unwrap(),
+                pair[1][3].parse::<i32>().unwrap()
+            );
+        }
+        for row in &rows {
+            assert!((1..=5).contains(&row[2].parse::<i32>().unwrap()));
+        }
+
+        let records = read_records(text.as_bytes()).unwrap();
+        assert_eq!(positions, records.len());
+        assert_eq!(Symbol::Red, records[0].to_move);
+        assert_eq!(vec![vec![Symbol::Empty; 5]; 4], records[0].cells().unwrap());
+        assert!(records.iter().all(|record| (0..5).contains(&record.column)));
+        for record in &records {
+            let (board, to_move) = Connect4::<5, 4>::from_grid(&record.position, 3).unwrap();
+            assert_eq!(record.to_move, to_move);
+            assert_eq!(record.position, board.to_grid(to_move));
+        }
+        for line in [
+            "5/5/5/5 R\t0\t1",
+            "5/5/5/x4 R\t0\t1\t1",
+            "5/5/5/5\t0\t1\t1",
+            "5/5/5/5 R\t0\t1\t2",
+        ] {
+            assert!(matches!(line.parse::<Record>(), Err(Error::InvalidLine(_))));
+        }
+    }
+}
@@ -0,0 +1,656 @@
+use crate::shared::game_outcome::GameOutcome;
+use crate::shared::rng::splitmix64;
+
+use super::bitboard::BitBoard;
+use super::board::Connect4;
+use super::position::Position;
+use super::strategy::Move;
+use super::symbol::Symbol;
+use super::transposition::Bound;
+use super::transposition::Entry;
+use super::transposition::TranspositionTable;
+
+/// Memory used by the transposition table of the default solver.
+const DEFAULT_TABLE_MEMORY: usize = 64 << 20;
+
+/// Value of a position when both players play perfectly, for the player to move.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Solution {
+    /// The player to move wins, with the `plies`-th move from now.
+    Win {
+        plies: i32,
+    },
+    Draw,
+    /// The opponent wins, with the `plies`-th move from now.
+    Loss {
+        plies: i32,
+    },
+}
+
+impl std::fmt::Display for Solution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Solution::Win { plies } => write!(f, "win in {plies} moves"),
+            Solution::Draw => write!(f, "draw"),
+            Solution::Loss { plies } => write!(f, "loss in {plies} moves"),
+        }
+    }
+}
+
+impl Solution {
+    /// Value of the board before the move leading to this solution, for the player who
+    /// made it.
+    fn before_move(self) -> Self {
+        match self {
+            Solution::Win { plies } => Solution::Loss { plies: plies + 1 },
+            Solution::Draw => Solution::Draw,
+            Solution::Loss { plies } => Solution::Win { plies: plies + 1 },
+        }
+    }
+
+    /// Orders the solutions from the worst to the best: the later a loss the better, and the
+    /// sooner a win the better.
+    fn preference(self) -> (i32, i32) {
+        match self {
+            Solution::Loss { plies } => (0, plies),
+            Solution::Draw => (1, 0),
+            Solution::Win { plies } => (2, -plies),
+        }
+    }
+}
+
+/// Computes the exact value of Connect4 positions.
+///
+/// The search works on scores rather than on solutions: winning with the `n`-th token of
+/// the board scores `(width * height + 2 - n) / 2`, so that the sooner the win the higher
+/// the score, and losing scores the opposite. The score is found by a bisection of null
+/// window searches, each of which only tells whether the score is above a given value.
+///
+/// See <http://blog.gamesolver.org/> for the details.
+pub struct Solver {
+    /// Upper bounds of the scores of the positions already searched.
+    table: TranspositionTable,
+
+    /// Width, height and winning tokens of the boards in `table`.
+    rules: Option<(usize, usize, i32)>,
+
+    /// Number of positions searched.
+    nodes: u64,
+}
+
+impl Solver {
+    /// Creates a solver whose transposition table uses at most `table_memory` bytes.
+    pub fn new(table_memory: usize) -> Self {
+        Self {
+            table: TranspositionTable::with_memory(table_memory),
+            rules: None,
+            nodes: 0,
+        }
+    }
+
+    pub fn nodes(&self) -> u64 {
+        self.nodes
+    }
+
+    /// Solves `board` for `symbol` to move, or returns `None` if the board doesn't fit in a
+    /// bitboard.
+    pub fn solve<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        board: &Connect4<WIDTH, HEIGHT>,
+        symbol: Symbol,
+    ) -> Option<Solution> {
+        let bitboard = BitBoard::from_board(board, symbol)?;
+        if bitboard.has_alignment(bitboard.current ^ bitboard.mask) {
+            return Some(Solution::Loss { plies: 0 });
+        }
+        if bitboard.has_alignment(bitboard.current) {
+            return Some(Solution::Win { plies: 0 });
+        }
+
+        let rules = Some((WIDTH, HEIGHT, board.winning_tokens));
+        if self.rules != rules {
+            self.table.clear();
+            self.rules = rules;
+        }
+
+        let node = Node::<WIDTH, HEIGHT> {
+            winning_tokens: bitboard.winning_tokens as usize,
+            current: bitboard.current,
+            mask: bitboard.mask,
+            moves: bitboard.mask.count_ones() as i32,
+        };
+        let cells = Node::<WIDTH, HEIGHT>::CELLS;
+        if node.moves == cells || node.is_dead() {
+            return Some(Solution::Draw);
+        }
+        if node.can_win_next() {
+            return Some(Solution::Win { plies: 1 });
+        }
+
+        let mut min = -(cells - node.moves) / 2;
+        let mut max = (cells + 1 - node.moves) / 2;
+        while min < max {
+            // Search closer to 0 first, as it narrows the window the most.
+            let mut med = min + (max - min) / 2;
+            if med <= 0 && min / 2 < med {
+                med = min / 2;
+            } else if med >= 0 && max / 2 > med {
+                med = max / 2;
+            }
+            let score = self.negamax(node, med, med + 1);
+            if score <= med {
+                max = score;
+            } else {
+                min = score;
+            }
+        }
+        Some(solution(min, node.moves, cells))
+    }
+
+    /// Score of `node` if it is within `[alpha, beta]`, otherwise a bound of the score
+    /// beyond the window.
+    ///
+    /// The player to move must not be able to win with the next move.
+    fn negamax<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        node: Node<WIDTH, HEIGHT>,
+        mut alpha: i32,
+        mut beta: i32,
+    ) -> i32 {
+        debug_assert!(alpha < beta);
+        debug_assert!(!node.can_win_next());
+        self.nodes += 1;
+        let cells = Node::<WIDTH, HEIGHT>::CELLS;
+
+        let moves = node.non_losing_moves();
+        if moves == 0 {
+            // The opponent wins with the next move.
+            return -(cells - node.moves) / 2;
+        }
+        if node.moves >= cells - 2 || node.is_dead() {
+            // The opponent can't win with the last token, or no one can win at all.
+            return 0;
+        }
+
+        // The opponent can't win with the next move, nor can we.
+        let min = -(cells - 2 - node.moves) / 2;
+        if alpha < min {
+            alpha = min;
+            if alpha >= beta {
+                return alpha;
+            }
+        }
+        // A position and its mirror image share the same entry.
+        let key = node.key();
+        let mirror = BitBoard::<WIDTH, HEIGHT>::mirror(key);
+        let hash = splitmix64(key.min(mirror));
+        let mut max = (cells - 1 - node.moves) / 2;
+        match self.table.get(hash) {
+            Some(Entry {
+                score,
+                bound: Bound::Lower,
+                ..
+            }) => alpha = alpha.max(score),
+            Some(Entry { score, .. }) => max = score,
+            None => (),
+        }
+        if beta > max {
+            beta = max;
+        }
+        if alpha >= beta {
+            return alpha;
+        }
+
+        // Sorted by insertion, children with the same number of threats stay in column order.
+        let mut children = [(0, 0); WIDTH];
+        let mut len = 0;
+        for column in column_order(WIDTH) {
+            // On a symmetric position, a column is as good as its mirror image.
+            if key == mirror && column > WIDTH - 1 - column {
+                continue;
+            }
+            let played = moves & Node::<WIDTH, HEIGHT>::column_mask(column);
+            if played != 0 {
+                let threats = node.threats(played);
+                let mut i = len;
+                while i > 0 && children[i - 1].1 < threats {
+                    children[i] = children[i - 1];
+                    i -= 1;
+                }
+                children[i] = (played, threats);
+                len += 1;
+            }
+        }
+        for &(played, _) in &children[..len] {
+            let score = -self.negamax(node.play(played), -beta, -alpha);
+            if score >= beta {
+                self.table.set(hash, Entry::new(score, 1, 0, Bound::Lower));
+                return score;
+            }
+            alpha = alpha.max(score);
+        }
+
+        // All the moves failed low, or one of them is exact: either way an upper bound.
+        self.table.set(hash, Entry::new(alpha, 1, 0, Bound::Upper));
+        alpha
+    }
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Self::new(DEFAULT_TABLE_MEMORY)
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// Plays the move of `symbol` with the best solution, the first column among equally
+    /// good ones. The move has no score.
+    ///
+    /// Returns `None` if the board is full or doesn't fit in a bitboard.
+    pub fn next_move_solved(&mut self, solver: &mut Solver, symbol: Symbol) -> Option<Move> {
+        let mut best: Option<(i32, Solution)> = None;
+        for column in 0..WIDTH as i32 {
+            let Some(play_column) = self.play_column(symbol, column) else {
+                continue;
+            };
+            let solution = match play_column.outcome {
+                GameOutcome::Win { .. } => Some(Solution::Win { plies: 1 }),
+                GameOutcome::Draw => Some(Solution::Draw),
+                GameOutcome::Ongoing => solver
+                    .solve(self, symbol.other())
+                    .map(Solution::before_move),
+            };
+            play_column.undo(self);
+            let solution = solution?;
+            if best.is_none_or(|(_, best)| best.preference() < solution.preference()) {
+                best = Some((column, solution));
+            }
+        }
+        let (column, _) = best?;
+        Some(Move {
+            column,
+            score: 0,
+            outcome: self.play_column(symbol, column)?.outcome,
+            pv: vec![column],
+        })
+    }
+}
+
+/// Position searched by the solver, laid out like a `BitBoard`.
+#[derive(Clone, Copy)]
+struct Node<const WIDTH: usize, const HEIGHT: usize> {
+    winning_tokens: usize,
+
+    /// Tokens of the player to move.
+    current: u64,
+
+    /// All the tokens.
+    mask: u64,
+
+    /// Number of tokens on the board.
+    moves: i32,
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Node<WIDTH, HEIGHT> {
+    const CELLS: i32 = (WIDTH * HEIGHT) as i32;
+
+    fn column_mask(column: usize) -> u64 {
+        ((1 << HEIGHT) - 1) << (column * (HEIGHT + 1))
+    }
+
+    /// The bottom cell of every column.
+    fn bottom_mask() -> u64 {
+        (0..WIDTH).fold(0, |mask, column| mask | 1 << (column * (HEIGHT + 1)))
+    }
+
+    /// All the cells of the board.
+    fn board_mask() -> u64 {
+        Self::bottom_mask() * ((1 << HEIGHT) - 1)
+    }
+
+    /// Unique key of the position: adding the tokens of the player to move to all the tokens
+    /// carries them above the top token of their column.
+    fn key(&self) -> u64 {
+        self.current + self.mask
+    }
+
+    /// The cells where a token can be played.
+    fn possible(&self) -> u64 {
+        (self.mask + Self::bottom_mask()) & Self::board_mask()
+    }
+
+    fn play(&self, played: u64) -> Self {
+        Self {
+            current: self.current ^ self.mask,
+            mask: self.mask | played,
+            moves: self.moves + 1,
+            ..*self
+        }
+    }
+
+    /// Whether no alignment can be completed anymore.
+    fn is_dead(&self) -> bool {
+        BitBoard::<WIDTH, HEIGHT>::dead(self.winning_tokens, self.current, self.mask)
+    }
+
+    fn can_win_next(&self) -> bool {
+        self.winning_cells(self.current, self.mask) & self.possible() != 0
+    }
+
+    /// The moves that don't let the opponent win with the next move, if any.
+    fn non_losing_moves(&self) -> u64 {
+        let mut possible = self.possible();
+        let opponent_wins = self.winning_cells(self.current ^ self.mask, self.mask);
+        let forced = possible & opponent_wins;
+        if forced != 0 {
+            if forced & (forced - 1) != 0 {
+                // The opponent has two ways to win, we can only block one.
+                return 0;
+            }
+            possible = forced;
+        }
+        // Playing right below a winning cell of the opponent lets them play there.
+        possible & !(opponent_wins >> 1)
+    }
+
+    /// Number of cells where the player to move could then win, once `played`.
+    fn threats(&self, played: u64) -> u32 {
+        self.winning_cells(self.current | played, self.mask | played)
+            .count_ones()
+    }
+
+    /// The empty cells that would complete an alignment of `tokens`.
+    fn winning_cells(&self, tokens: u64, mask: u64) -> u64 {
+        let cells = if self.winning_tokens == 4 {
+            Self::winning_cells_4(tokens)
+        } else {
+            self.winning_cells_n(tokens)
+        };
+        cells & Self::board_mask() & !mask
+    }
+
+    /// `winning_cells` for the standard rules, unrolled as this is where the solver spends
+    /// most of its time.
+    fn winning_cells_4(tokens: u64) -> u64 {
+        // Vertical: 3 tokens below.
+        let mut cells = (tokens << 1) & (tokens << 2) & (tokens << 3);
+        // Horizontal and both diagonals.
+        for shift in [HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+            let before = (tokens << shift) & (tokens << (2 * shift));
+            cells |= before & (tokens << (3 * shift));
+            cells |= before & (tokens >> shift);
+            let after = (tokens >> shift) & (tokens >> (2 * shift));
+            cells |= after & (tokens << shift);
+            cells |= after & (tokens >> (3 * shift));
+        }
+        cells
+    }
+
+    /// `winning_cells` for any number of winning tokens.
+    ///
+    /// A cell completes an alignment when it is preceded by `before` tokens and followed by
+    /// the remaining ones in some direction. Shifting the tokens by `i` cells in a direction
+    /// moves the token `i` cells before each cell onto it.
+    fn winning_cells_n(&self, tokens: u64) -> u64 {
+        let shifted = |i: usize, shift: usize, left: bool| {
+            let bits = (i * shift) as u32;
+            if left {
+                tokens.checked_shl(bits).unwrap_or(0)
+            } else {
+                tokens.checked_shr(bits).unwrap_or(0)
+            }
+        };
+        let mut cells = 0;
+        // Vertical, horizontal and both diagonals.
+        for shift in [1, HEIGHT + 1, HEIGHT, HEIGHT + 2] {
+            for before in 0..self.winning_tokens {
+                let preceded = (1..=before).fold(u64::MAX, |c, i| c & shifted(i, shift, true));
+                let after = self.winning_tokens - before;
+                cells |= (1..after).fold(preceded, |c, i| c & shifted(i, shift, false));
+            }
+        }
+        cells
+    }
+}
+
+/// Columns from the center of the board to the edges, where alignments are less likely.
+fn column_order(width: usize) -> impl Iterator<Item = usize> {
+    (0..width).map(move |i| {
+        if i % 2 == 0 {
+            width / 2 + i / 2
+        } else {
+            width / 2 - i.div_ceil(2)
+        }
+    })
+}
+
+/// The solution of a position with `moves` tokens on a board of `cells` cells, given its
+/// score.
+fn solution(score: i32, moves: i32, cells: i32) -> Solution {
+    // The player to move plays the tokens of opposite parity to `moves`.
+    let last_token = |score: i32, parity: i32| {
+        let token = cells + 2 - 2 * score;
+        if (token - moves) % 2 == parity {
+            token
+        } else {
+            token - 1
+        }
+    };
+    match score {
+        0 => Solution::Draw,
+        score if score > 0 => Solution::Win {
+            plies: last_token(score, 1) - moves,
+        },
+        score => Solution::Loss {
+            plies: last_token(-score, 0) - moves,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::connect4::position::Position;
+    use crate::shared::game_outcome::GameOutcome;
+
+    use super::*;
+
+    #[test]
+    fn column_order() {
+        assert_eq!(
+            vec![3, 2, 4, 1, 5, 0, 6],
+            super::column_order(7).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![2, 1, 3, 0], super::column_order(4).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn solution() {
+        // 42 cells, Red plays the odd tokens.
+        assert_eq!(Solution::Win { plies: 1 }, super::solution(21, 0, 42));
+        assert_eq!(Solution::Win { plies: 41 }, super::solution(1, 0, 42));
+        assert_eq!(Solution::Loss { plies: 2 }, super::solution(-21, 0, 42));
+        assert_eq!(Solution::Loss { plies: 42 }, super::solution(-1, 0, 42));
+        assert_eq!(Solution::Win { plies: 1 }, super::solution(21, 1, 42));
+        assert_eq!(Solution::Win { plies: 3 }, super::solution(20, 1, 42));
+        assert_eq!(Solution::Draw, super::solution(0, 7, 42));
+    }
+
+    #[test]
+    fn immediate() {
+        let mut solver = Solver::new(1 << 16);
+        let mut board = Connect4::<7, 6>::new(4);
+        assert_eq!(None, solver.solve(&Connect4::<8, 8>::new(4), Symbol::Red));
+
+        for column in [0, 1, 0, 1, 0, 1] {
+            board.play_column(Symbol::Red, column);
+        }
+        assert_eq!(
+            Some(Solution::Win { plies: 1 }),
+            solver.solve(&board, Symbol::Red)
+        );
+        assert_eq!(
+            Some(Solution::Loss { plies: 2 }),
+            solver.solve(&board, Symbol::Yellow)
+        );
+
+        board.play_column(Symbol::Red, 0);
+        assert_eq!(
+            Some(Solution::Loss { plies: 0 }),
+            solver.solve(&board, Symbol::Yellow)
+        );
+    }
+
+    #[test]
+    fn next_move_solved() {
+        let mut solver = Solver::new(1 << 16);
+        let mut board = Connect4::<5, 4>::new(3);
+        for (column, symbol) in [(1, Symbol::Red), (1, Symbol::Yellow)] {
+            board.play_column(symbol, column);
+        }
+        let expected = solver.solve(&board, Symbol::Red).unwrap();
+        board.next_move_solved(&mut solver, Symbol::Red).unwrap();
+        assert_eq!(
+            Some(expected),
+            solver
+                .solve(&board, Symbol::Yellow)
+                .map(Solution::before_move)
+        );
+
+        let mut board = Connect4::<7, 6>::new(4);
+        for column in [0, 1, 0, 1, 0] {
+            board.play_column(Symbol::Red, column);
+        }
+        let solved_move = board.next_move_solved(&mut solver, Symbol::Red).unwrap();
+        assert_eq!((0, vec![0]), (solved_move.column, solved_move.pv));
+        assert!(solved_move.outcome.is_over());
+        assert!(Connect4::<8, 8>::new(4)
+            .next_move_solved(&mut solver, Symbol::Red)
+            .is_none());
+    }
+
+    #[test]
+    fn dead() {
+        let mut solver = Solver::new(1 << 16);
+        let mut board = Connect4::<4, 2>::new(3);
+        for (column, symbol) in [
+            (0, Symbol::Red),
+            (1, Symbol::Yellow),
+            (2, Symbol::Red),
+            (3, Symbol::Yellow),
+            (1, Symbol::Red),
+        ] {
+            board.play_column(symbol, column);
+        }
+        assert_eq!(Some(Solution::Draw), solver.solve(&board, Symbol::Yellow));
+        let nodes = solver.nodes();
+
+        board.play_column(Symbol::Yellow, 2);
+        assert_eq!(Some(Solution::Draw), solver.solve(&board, Symbol::Red));
+        assert_eq!(nodes, solver.nodes());
+    }
+
+    #[test]
+    fn same_as_brute_force() {
+        fn check<const WIDTH: usize, const HEIGHT: usize>(winning_tokens: i32) {
+            let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+            let mut solver = Solver::new(1 << 16);
+            let mut solutions = HashMap::new();
+            let mut symbol = Symbol::Red;
+            let columns = [1, 2, 2, 0, 3, 1, 0, 3, 3, 4, 1];
+            for column in columns.map(|column| column % WIDTH as i32) {
+                let expected = brute_force(&mut board, symbol, &mut solutions);
+                assert_eq!(Some(expected), solver.solve(&board, symbol), "\n{board}");
+                match board.play_column(symbol, column) {
+                    Some(play_column) if !play_column.outcome.is_over() => (),
+                    _ => break,
+                }
+                symbol = symbol.other();
+            }
+        }
+        check::<4, 4>(3);
+        check::<5, 4>(3);
+        check::<4, 4>(4);
+    }
+
+    #[test]
+    fn winning_cells() {
+        let mut board = BitBoard::<7, 6>::new(4, Symbol::Red);
+        let mut symbol = Symbol::Red;
+        for column in [
+            3, 3, 4, 2, 2, 5, 1, 1, 6, 6, 6, 6, 6, 6, 0, 0, 4, 4, 4, 5, 5, 1,
+        ] {
+            board.play_column(symbol, column);
+            symbol = symbol.other();
+            let node = Node::<7, 6> {
+                winning_tokens: 4,
+                current: board.current,
+                mask: board.mask,
+                moves: board.mask.count_ones() as i32,
+            };
+            let empty = Node::<7, 6>::board_mask() & !node.mask;
+            for tokens in [node.current, node.current ^ node.mask] {
+                assert_eq!(
+                    Node::<7, 6>::winning_cells_4(tokens) & empty,
+                    node.winning_cells_n(tokens) & empty,
+                    "\n{board}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "takes about 15 minutes"]
+    fn empty_7x6() {
+        let mut solver = Solver::default();
+        let board = Connect4::<7, 6>::new(4);
+        let start = std::time::Instant::now();
+        let solution = solver.solve(&board, Symbol::Red);
+        println!("{} nodes in {:?}", solver.nodes(), start.elapsed());
+        assert_eq!(Some(Solution::Win { plies: 41 }), solution);
+    }
+
+    /// Solves `board` by trying all the moves, memoizing the solutions by hash.
+    fn brute_force(
+        board: &mut impl Position,
+        symbol: Symbol,
+        solutions: &mut HashMap<u64, Solution>,
+    ) -> Solution {
+        let hash = board.hash() ^ crate::connect4::zobrist::side_key(symbol);
+        if let Some(solution) = solutions.get(&hash) {
+            return *solution;
+        }
+        let mut best = None;
+        for column in 0..board.width() {
+            let Some(play_column) = board.play_column(symbol, column) else {
+                continue;
+            };
+            let solution = match play_column.outcome {
+                GameOutcome::Win { .. } => Solution::Win { plies: 1 },
+                GameOutcome::Draw => Solution::Draw,
+                GameOutcome::Ongoing => match brute_force(board, symbol.other(), solutions) {
+                    Solution::Win { plies } => Solution::Loss { plies: plies + 1 },
+                    Solution::Draw => Solution::Draw,
+                    Solution::Loss { plies } => Solution::Win { plies: plies + 1 },
+                },
+            };
+            play_column.undo(board);
+            if best.is_none_or(|best| rank(best) < rank(solution)) {
+                best = Some(solution);
+            }
+        }
+        let solution = best.unwrap_or(Solution::Draw);
+        solutions.insert(hash, solution);
+        solution
+    }
+
+    /// Orders solutions from the worst to the best for the player to move.
+    fn rank(solution: Solution) -> i32 {
+        match solution {
+            Solution::Win { plies } => 1000 - plies,
+            Solution::Draw => 0,
+            Solution::Loss { plies } => plies - 1000,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt::Display;
+use std::time::Duration;
+
+/// Statistics of the searches of a move.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchStats {
+    /// Number of positions searched.
+    pub nodes: u64,
+
+    /// Number of positions scored without searching further: the positions at the maximum
+    /// depth and the ends of games.
+    pub leaves: u64,
+
+    /// Number of positions whose search stopped before the last column, because a column
+    /// was already too good for the opponent to allow it.
+    pub cutoffs: u64,
+
+    /// Number of the positions searched by the helper threads, out of `nodes`.
+    pub helper_nodes: u64,
+
+    /// Deepest position reached, in moves from the board.
+    pub depth: i32,
+
+    /// Time spent searching.
+    pub elapsed: Duration,
+}
+
+impl SearchStats {
+    /// Branching factor of a uniform tree with as many positions and the same depth.
+    pub fn effective_branching_factor(&self) -> f64 {
+        if self.depth == 0 {
+            0.
+        } else {
+            (self.nodes as f64).powf(1. / self.depth as f64)
+        }
+    }
+
+    pub fn nodes_per_second(&self) -> f64 {
+        if self.elapsed.is_zero() {
+            0.
+        } else {
+            self.nodes as f64 / self.elapsed.as_secs_f64()
+        }
+    }
+
+    /// Adds the statistics of a search that ran at the same time, in another thread.
+    pub(super) fn add_parallel(&mut self, other: &SearchStats) {
+        self.nodes += other.nodes;
+        self.helper_nodes += other.nodes;
+        self.leaves += other.leaves;
+        self.cutoffs += other.cutoffs;
+        self.depth = self.depth.max(other.depth);
+    }
+}
+
+impl Display for SearchStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} positions, {} leaves, {} cutoffs, {} moves deep, branching factor {:.2}, {:.3}s, {:.0} positions/s",
+            self.nodes,
+            self.leaves,
+            self.cutoffs,
+            self.depth,
+            self.effective_branching_factor(),
+            self.elapsed.as_secs_f64(),
+            self.nodes_per_second()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let stats = SearchStats {
+            nodes: 1000,
+            leaves: 800,
+            cutoffs: 150,
+            helper_nodes: 0,
+            depth: 3,
+            elapsed: Duration::from_millis(250),
+        };
+        assert_eq!(
+            "1000 positions, 800 leaves, 150 cutoffs, 3 moves deep, branching factor 10.00, 0.250s, 4000 positions/s",
+            stats.to_string()
+        );
+        assert_eq!(
+            "0 positions, 0 leaves, 0 cutoffs, 0 moves deep, branching factor 0.00, 0.000s, 0 positions/s",
+            SearchStats::default().to_string()
+        );
+    }
+}
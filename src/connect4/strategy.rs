@@ -1,58 +1,556 @@
-use crate::shared::board::board_size::BoardSize;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::Duration;
+use std::time::Instant;
 
+use crate::shared::board::board_all_points::BoardAllPoints;
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::game_outcome::GameOutcome;
+
+use super::bitboard::BitBoard;
 use super::board::Connect4;
+use super::engine::Engine;
+use super::game::PlayColumn;
+use super::personality::Personality;
+use super::position::mirror_column;
+use super::position::Position;
 use super::symbol::Symbol;
+use super::transposition::Bound;
+use super::transposition::Entry;
+use super::zobrist;
+
+/// Bound of the alpha-beta window. Not `i32::MIN` so that it can be negated.
+const INFINITY: i32 = i32::MAX;
+
+/// Change of the score of a move that wins the game, with the `Evaluator` of an engine.
+const WIN_SCORE: i32 = 100_000_000;
 
 impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    #[cfg(test)]
     pub fn next_move(&mut self, symbol: Symbol, max_depth: i32) -> Option<Move> {
-        let next_move = self.next_move_impl(symbol, 0, max_depth);
-        if let Some(next_move) = next_move.as_ref() {
-            *self.get_dropped_pos_mut(next_move.column).unwrap().symbol = symbol;
+        self.next_move_with(&mut Engine::default(), symbol, max_depth)
+    }
+
+    /// Plays the best move found by `engine`, reusing what it learnt from previous searches.
+    ///
+    /// The move comes from the opening book of `engine` when it has the board, searched at
+    /// least `max_depth` moves ahead. Otherwise the search runs on a `BitBoard` copy of the
+    /// board when it fits, with the threads of `engine`.
+    ///
+    /// Unless the personality of `engine` is `Personality::Best`, the move is rather chosen
+    /// by the personality among all the columns, each of them searched, without the book.
+    pub fn next_move_with(
+        &mut self,
+        engine: &mut Engine,
+        symbol: Symbol,
+        max_depth: i32,
+    ) -> Option<Move> {
+        engine.stats = Default::default();
+        let next_move = match engine.personality {
+            Personality::Best => self
+                .book_move(engine, symbol, max_depth)
+                .or_else(|| self.search(engine, symbol, max_depth)),
+            personality => {
+                let moves = self.analyze(engine, symbol, max_depth);
+                personality.choose(moves, &mut engine.rng)
+            }
+        };
+        self.play_move(symbol, next_move)
+    }
+
+    /// Plays the best move found by `engine` within `budget`.
+    ///
+    /// The search is deepened one move at a time until the deadline, and the move of the
+    /// last completed search is played. The search at depth 0 always completes. When the
+    /// opening book of `engine` has the board, its move stands for the searches up to the
+    /// depth of the book, and the search goes on deeper.
+    pub fn next_move_timed(
+        &mut self,
+        engine: &mut Engine,
+        symbol: Symbol,
+        budget: Duration,
+    ) -> Option<TimedMove> {
+        engine.stats = Default::default();
+        let deadline = Instant::now() + budget;
+        let empty_cells = self
+            .all_points()
+            .filter(|point| self.get(point).symbol == Symbol::Empty)
+            .count() as i32;
+        let mut best_move = engine.book.as_ref().and_then(|book| {
+            let depth = book.depth();
+            Some((self.book_move(engine, symbol, depth)?, depth))
+        });
+        let first_depth = best_move.as_ref().map_or(0, |(_, depth)| depth + 1);
+        for depth in first_depth..empty_cells {
+            if depth > 0 {
+                if Instant::now() >= deadline {
+                    break;
+                }
+                engine.deadline = Some(deadline);
+            }
+            let next_move = self.search(engine, symbol, depth);
+            if engine.stopped {
+                break;
+            }
+            best_move = next_move.map(|next_move| (next_move, depth));
+        }
+        engine.deadline = None;
+        engine.stopped = false;
+
+        let (next_move, depth) = best_move?;
+        self.play_move(symbol, Some(next_move))
+            .map(|next_move| TimedMove { next_move, depth })
+    }
+
+    /// The move of the opening book of `engine`, if it has the board searched at least
+    /// `max_depth` moves ahead.
+    fn book_move(&self, engine: &Engine, symbol: Symbol, max_depth: i32) -> Option<Move> {
+        let book = engine
+            .book
+            .as_ref()
+            .filter(|book| book.depth() >= max_depth)?;
+        let book_move = book.get(self, symbol)?;
+        Some(Move {
+            column: book_move.column,
+            score: book_move.score,
+            outcome: GameOutcome::Ongoing,
+            pv: vec![book_move.column],
+        })
+    }
+
+    /// Searches the best move and its principal variation without playing it.
+    ///
+    /// The statistics of the search are added to those of `engine`.
+    pub(super) fn search(
+        &mut self,
+        engine: &mut Engine,
+        symbol: Symbol,
+        max_depth: i32,
+    ) -> Option<Move> {
+        let bitboard = BitBoard::from_board(self, symbol).filter(|_| engine.bitboard);
+        if let Some(bitboard) = bitboard {
+            return search_bitboard(bitboard, engine, symbol, max_depth);
         }
+        let start = Instant::now();
+        engine.ordering.new_search(WIDTH as i32, HEIGHT as i32);
+        engine.search_depth = max_depth;
+        let params = self.params();
+        self.set_params(engine.params);
+        let next_move = next_move_impl(self, engine, symbol, 0, max_depth, -INFINITY, INFINITY);
+        let next_move = next_move.map(|mut next_move| {
+            next_move.pv = principal_variation(self, engine, symbol, max_depth);
+            next_move
+        });
+        self.set_params(params);
+        engine.stats.elapsed += start.elapsed();
         next_move
     }
 
-    fn next_move_impl(&mut self, symbol: Symbol, score: i32, max_depth: i32) -> Option<Move> {
-        let mut best_move: Option<Move> = None;
-        for column in 0..self.width() {
-            if let Some(play_column) = self.play_column(symbol, column) {
-                if play_column.end_of_game || max_depth == 0 {
-                    Self::update_best_move(
-                        &mut best_move,
-                        column,
-                        score + play_column.delta_score,
-                        play_column.end_of_game,
-                    );
-                } else if let Some(opponent_play) = self.next_move_impl(
-                    symbol.other(),
-                    score - play_column.delta_score,
-                    max_depth - 1,
-                ) {
-                    Self::update_best_move(
-                        &mut best_move,
-                        column,
-                        score - opponent_play.score,
-                        play_column.end_of_game,
-                    );
+    fn play_move(&mut self, symbol: Symbol, mut next_move: Option<Move>) -> Option<Move> {
+        if let Some(next_move) = next_move.as_mut() {
+            next_move.outcome = self.play_column(symbol, next_move.column).unwrap().outcome;
+        }
+        next_move
+    }
+}
+
+/// Searches the best move of `symbol` on `position` and its principal variation, like
+/// `Connect4::search`.
+pub(super) fn search_bitboard<const WIDTH: usize, const HEIGHT: usize>(
+    mut position: BitBoard<WIDTH, HEIGHT>,
+    engine: &mut Engine,
+    symbol: Symbol,
+    max_depth: i32,
+) -> Option<Move> {
+    let start = Instant::now();
+    engine.ordering.new_search(WIDTH as i32, HEIGHT as i32);
+    engine.search_depth = max_depth;
+    position.params = engine.params;
+    let next_move = search_parallel(position.clone(), engine, symbol, max_depth);
+    let next_move = next_move.map(|mut next_move| {
+        next_move.pv = principal_variation(&mut position, engine, symbol, max_depth);
+        next_move
+    });
+    engine.stats.elapsed += start.elapsed();
+    next_move
+}
+
+/// Principal variation of the search of `position` that just completed, `max_depth` moves
+/// ahead.
+///
+/// The variation of the search stops at the positions found in the transposition table. It
+/// goes on with the columns stored in the table, then with the column that scores the most
+/// on its own at the last move, until the depth searched or the end of the game.
+fn principal_variation(
+    position: &mut impl Position,
+    engine: &Engine,
+    symbol: Symbol,
+    max_depth: i32,
+) -> Vec<i32> {
+    let mut pv = engine.pv(max_depth).to_vec();
+    let mut played = vec![];
+    let mut hashes = HashSet::new();
+    let mut symbol = symbol;
+    let mut over = false;
+    for (i, column) in pv.iter().enumerate() {
+        if over || i > max_depth as usize {
+            break;
+        }
+        let Some(play_column) = position.play_column(symbol, *column) else {
+            break;
+        };
+        over = play_column.outcome.is_over() || position.is_dead();
+        played.push(play_column);
+        symbol = symbol.other();
+    }
+    pv.truncate(played.len());
+    let width = position.width() as usize;
+    while !over && pv.len() <= max_depth as usize {
+        let column = if pv.len() < max_depth as usize {
+            let hash = position.hash() ^ zobrist::side_key(symbol);
+            let mirror_hash = position.mirror_hash() ^ zobrist::side_key(symbol);
+            if !hashes.insert(hash.min(mirror_hash)) {
+                break;
+            }
+            let Some(entry) = engine.table.get(hash.min(mirror_hash)) else {
+                break;
+            };
+            mirror_column(entry.column as i32, width, mirror_hash < hash)
+        } else {
+            // The first of the columns that score the most, like the search at depth 0.
+            let mut best: Option<(i32, i32)> = None;
+            for column in 0..position.width() {
+                if let Some(play_column) = position.play_column(symbol, column) {
+                    play_column.undo(position);
+                    if best.is_none_or(|(_, score)| score < play_column.delta_score) {
+                        best = Some((column, play_column.delta_score));
+                    }
                 }
-                play_column.undo(self);
+            }
+            let Some((column, _)) = best else {
+                break;
             };
+            column
+        };
+        let Some(play_column) = position.play_column(symbol, column) else {
+            break;
+        };
+        over = play_column.outcome.is_over() || position.is_dead();
+        played.push(play_column);
+        pv.push(column);
+        symbol = symbol.other();
+    }
+    for play_column in played.iter().rev() {
+        play_column.undo(position);
+    }
+    pv
+}
+
+/// Lazy SMP search of `position` with the threads of `engine`.
+///
+/// Helper threads search copies of the position to the same depth, each one starting with
+/// another column, and only share what they find through the transposition table, where
+/// the main search picks up their results. Entries of deeper searches would change its
+/// score, so the threads only change how fast the score is found. The move is the one of
+/// the main search, after which the helpers stop.
+fn search_parallel<const WIDTH: usize, const HEIGHT: usize>(
+    mut position: BitBoard<WIDTH, HEIGHT>,
+    engine: &mut Engine,
+    symbol: Symbol,
+    max_depth: i32,
+) -> Option<Move> {
+    let abort = Arc::new(AtomicBool::new(false));
+    std::thread::scope(|scope| {
+        let helpers: Vec<_> = (1..engine.threads)
+            .map(|thread| {
+                let mut helper = engine.helper(abort.clone());
+                helper.ordering.new_search(WIDTH as i32, HEIGHT as i32);
+                helper.ordering.rotation = thread;
+                let mut position = position.clone();
+                helper.search_depth = max_depth;
+                scope.spawn(move || {
+                    next_move_impl(
+                        &mut position,
+                        &mut helper,
+                        symbol,
+                        0,
+                        max_depth,
+                        -INFINITY,
+                        INFINITY,
+                    );
+                    helper.stats
+                })
+            })
+            .collect();
+        let next_move = next_move_impl(
+            &mut position,
+            engine,
+            symbol,
+            0,
+            max_depth,
+            -INFINITY,
+            INFINITY,
+        );
+        abort.store(true, Ordering::Relaxed);
+        for helper in helpers {
+            engine.stats.add_parallel(&helper.join().unwrap());
+        }
+        next_move
+    })
+}
+
+/// Negamax search with alpha-beta pruning.
+///
+/// Columns are searched best first according to the move ordering heuristics of `engine`,
+/// so that the good moves raise `alpha` early and the other ones get pruned. The first
+/// column is searched with the full `[alpha, beta]` window, the following ones with a null
+/// window that only proves they are not better. A column that fails high is searched
+/// again with the full window to get its exact score.
+///
+/// `score` is the evaluation of the board from the point of view of `symbol`. The opponent
+/// is searched from the opposite of the score after the move, so that the changes of the
+/// score of all the moves add up. The search before alpha-beta pruning searched it from
+/// `score - delta_score` instead, which lost the change of the first move from depth 2 on.
+///
+/// Returns `None` if the search ran out of time, in which case nothing is stored in the
+/// transposition table.
+fn next_move_impl(
+    position: &mut impl Position,
+    engine: &mut Engine,
+    symbol: Symbol,
+    score: i32,
+    max_depth: i32,
+    mut alpha: i32,
+    mut beta: i32,
+) -> Option<Move> {
+    if engine.next_node() {
+        return None;
+    }
+    engine.clear_pv(max_depth);
+    let ply = engine.search_depth - max_depth;
+    engine.stats.depth = engine.stats.depth.max(ply);
+    // A position and its mirror image share the same entry, whose column is the one of the
+    // position with the smallest hash.
+    let hash = position.hash() ^ zobrist::side_key(symbol);
+    let mirror_hash = position.mirror_hash() ^ zobrist::side_key(symbol);
+    let mirrored = mirror_hash < hash;
+    let width = position.width() as usize;
+    let table_entry = if max_depth > 0 {
+        engine.table.get(hash.min(mirror_hash))
+    } else {
+        None
+    };
+    if let Some(entry) = table_entry.filter(|entry| entry.depth as i32 >= max_depth) {
+        let table_move = Move {
+            column: mirror_column(entry.column as i32, width, mirrored),
+            score: add_score(score, entry.score),
+            outcome: GameOutcome::Ongoing,
+            pv: vec![],
+        };
+        match entry.bound {
+            Bound::Exact => {
+                engine.update_pv(max_depth, table_move.column, true);
+                return Some(table_move);
+            }
+            Bound::Lower => alpha = alpha.max(table_move.score),
+            Bound::Upper => beta = beta.min(table_move.score),
+        }
+        if alpha >= beta {
+            engine.update_pv(max_depth, table_move.column, true);
+            return Some(table_move);
         }
-        best_move
     }
+    let table_column = table_entry.map(|entry| mirror_column(entry.column as i32, width, mirrored));
 
-    fn update_best_move(best_move: &mut Option<Move>, column: i32, score: i32, end_of_game: bool) {
-        if if let Some(best_move) = best_move {
-            best_move.score < score
+    // On a symmetric position, a column is as good as its mirror image.
+    let symmetric = position.is_symmetric();
+    let evaluator = engine.evaluator.as_deref();
+    let evaluation = evaluator.map(|evaluator| evaluator.evaluate(position, symbol));
+    let mut play_columns: Vec<PlayColumn> = (0..position.width())
+        .filter(|column| !symmetric || *column <= mirror_column(*column, width, true))
+        .filter_map(|column| {
+            let mut play_column = position.play_column(symbol, column)?;
+            if let (Some(evaluator), Some(evaluation)) = (evaluator, evaluation) {
+                play_column.delta_score = match play_column.outcome {
+                    GameOutcome::Win { .. } => WIN_SCORE,
+                    GameOutcome::Draw => -evaluation,
+                    GameOutcome::Ongoing => evaluator.evaluate(position, symbol) - evaluation,
+                };
+            }
+            play_column.undo(position);
+            Some(play_column)
+        })
+        .collect();
+    if play_columns.is_empty() {
+        engine.stats.leaves += 1;
+        return None;
+    }
+    engine
+        .ordering
+        .sort(&mut play_columns, symbol, max_depth, table_column);
+    if ply == 0 {
+        let rotation = engine.ordering.rotation % play_columns.len();
+        play_columns.rotate_left(rotation);
+    }
+    if max_depth == 0 {
+        engine.stats.leaves += play_columns.len() as u64;
+        engine.stats.depth = engine.stats.depth.max(ply + 1);
+        // The first of the best moves, whatever the order.
+        let best_move = play_columns
+            .iter()
+            .min_by_key(|play_column| Reverse(play_column.delta_score))
+            .map(|play_column| Move {
+                column: play_column.position.x,
+                score: add_score(score, play_column.delta_score),
+                outcome: play_column.outcome,
+                pv: vec![],
+            });
+        if let Some(best_move) = best_move.as_ref() {
+            engine.update_pv(max_depth, best_move.column, true);
+        }
+        return best_move;
+    }
+
+    let original_alpha = alpha;
+    let mut best_move: Option<Move> = None;
+    for PlayColumn {
+        position: point,
+        delta_score,
+        ..
+    } in play_columns
+    {
+        let column = point.x;
+        let play_column = position.play_column(symbol, column).unwrap();
+        let score = add_score(score, delta_score);
+        // Once no line can be filled, the game is a draw whatever the moves left.
+        let over = play_column.outcome.is_over() || position.is_dead();
+        let move_score = if over {
+            engine.stats.leaves += 1;
+            engine.stats.depth = engine.stats.depth.max(ply + 1);
+            // A draw scores as the board searched, however the lines were scored on the way.
+            if play_column.outcome.winner().is_some() {
+                score
+            } else {
+                0
+            }
+        } else if best_move.is_none() {
+            -opponent_score(position, engine, symbol, score, max_depth, -beta, -alpha)
         } else {
-            true
-        } {
-            *best_move = Some(Move {
-                column,
+            let move_score = -opponent_score(
+                position,
+                engine,
+                symbol,
                 score,
-                end_of_game,
-            })
+                max_depth,
+                -alpha - 1,
+                -alpha,
+            );
+            if alpha < move_score && move_score < beta {
+                -opponent_score(
+                    position,
+                    engine,
+                    symbol,
+                    score,
+                    max_depth,
+                    -beta,
+                    -move_score,
+                )
+            } else {
+                move_score
+            }
+        };
+        play_column.undo(position);
+        if engine.stopped {
+            return None;
+        }
+        if best_move
+            .as_ref()
+            .is_none_or(|best_move| best_move.score < move_score)
+        {
+            engine.update_pv(max_depth, column, over);
         }
+        update_best_move(&mut best_move, column, move_score, play_column.outcome);
+        alpha = alpha.max(move_score);
+        if alpha >= beta {
+            engine.stats.cutoffs += 1;
+            engine.ordering.cutoff(symbol, max_depth, &play_column);
+            break;
+        }
+    }
+
+    if let Some(best_move) = best_move.as_ref() {
+        engine.table.set(
+            hash.min(mirror_hash),
+            Entry::new(
+                add_score(best_move.score, -score),
+                max_depth as u8,
+                mirror_column(best_move.column, width, mirrored) as u8,
+                if best_move.score <= original_alpha {
+                    Bound::Upper
+                } else if best_move.score >= beta {
+                    Bound::Lower
+                } else {
+                    Bound::Exact
+                },
+            ),
+        );
+    }
+    best_move
+}
+
+/// `score` changed by `delta_score`, within the alpha-beta window so that it can be
+/// negated.
+///
+/// The lines of heavy weights score more than the window holds, in which case the score
+/// stops at its bound.
+fn add_score(score: i32, delta_score: i32) -> i32 {
+    score.saturating_add(delta_score).clamp(-INFINITY, INFINITY)
+}
+
+/// Score of the best reply of the opponent of `symbol`, once `symbol` has played.
+///
+/// When the board is full, the opponent can't play and the position is scored as is.
+fn opponent_score(
+    position: &mut impl Position,
+    engine: &mut Engine,
+    symbol: Symbol,
+    score: i32,
+    max_depth: i32,
+    alpha: i32,
+    beta: i32,
+) -> i32 {
+    next_move_impl(
+        position,
+        engine,
+        symbol.other(),
+        -score,
+        max_depth - 1,
+        alpha,
+        beta,
+    )
+    .map_or(-score, |opponent_move| opponent_move.score)
+}
+
+fn update_best_move(
+    best_move: &mut Option<Move>,
+    column: i32,
+    score: i32,
+    outcome: GameOutcome<Symbol>,
+) {
+    if if let Some(best_move) = best_move {
+        best_move.score < score
+    } else {
+        true
+    } {
+        *best_move = Some(Move {
+            column,
+            score,
+            outcome,
+            pv: vec![],
+        })
     }
 }
 
@@ -60,11 +558,35 @@ impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
 pub struct Move {
     pub column: i32,
     pub score: i32,
-    pub end_of_game: bool,
+
+    /// Whether the move won the game, filled the board, or neither.
+    pub outcome: GameOutcome<Symbol>,
+
+    /// Principal variation: the columns of the best moves of both players from the board,
+    /// starting with `column`.
+    ///
+    /// It goes as deep as the search, or to the end of the game. Past the positions the
+    /// search found in the transposition table, it follows the columns stored there.
+    pub pv: Vec<i32>,
+}
+
+/// Move found by a search bounded by time.
+#[derive(Debug)]
+pub struct TimedMove {
+    pub next_move: Move,
+
+    /// Depth of the last completed search.
+    pub depth: i32,
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::connect4::book::Book;
+    use crate::connect4::eval::EvalParams;
+    use crate::connect4::ordering::Heuristic;
+    use crate::connect4::ordering::MoveOrdering;
+    use crate::shared::board::board_size::BoardSize;
+
     use super::*;
 
     #[test]
@@ -94,4 +616,681 @@ mod tests {
             format!("\n{board}")
         );
     }
+
+    #[test]
+    fn same_move_as_minimax() {
+        for columns in test_games() {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            for (ply, column) in columns.into_iter().enumerate() {
+                let max_depths = if ply % 3 == 0 { 0..=5 } else { 0..=1 };
+                for max_depth in max_depths {
+                    let mut minimax_nodes = 0;
+                    let expected =
+                        minimax(&mut board, symbol, 0, max_depth, &mut minimax_nodes).unwrap();
+                    // Minimax picks the leftmost of the best columns.
+                    let mut engine = test_engine();
+                    engine.ordering =
+                        MoveOrdering::new(vec![Heuristic::TableMove, Heuristic::Score]);
+                    let actual = next_move_impl(
+                        &mut board,
+                        &mut engine,
+                        symbol,
+                        0,
+                        max_depth,
+                        -INFINITY,
+                        INFINITY,
+                    )
+                    .unwrap();
+                    assert_eq!(
+                        (expected.column, expected.score),
+                        (actual.column, actual.score),
+                        "max_depth={max_depth}\n{board}"
+                    );
+                    if max_depth <= 1 {
+                        let baseline = baseline(&mut board, symbol, 0, max_depth).unwrap();
+                        assert_eq!(
+                            (baseline.column, baseline.score),
+                            (actual.column, actual.score),
+                            "max_depth={max_depth}\n{board}"
+                        );
+                    }
+                    if max_depth >= 3 {
+                        assert!(
+                            engine.stats.nodes < minimax_nodes,
+                            "max_depth={max_depth}: {} nodes, {minimax_nodes} with minimax\n{board}",
+                            engine.stats.nodes
+                        );
+                    }
+                }
+                if board.play_column(symbol, column).unwrap().outcome.is_over() {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+        }
+    }
+
+    #[test]
+    fn dead_position() {
+        let mut board = Connect4::<4, 4>::new(4);
+        let mut symbol = Symbol::Red;
+        for column in [1, 1, 3, 0, 3, 3, 3, 2, 0, 0, 2] {
+            board.play_column(symbol, column);
+            symbol = symbol.other();
+        }
+        // Whatever the moves, no line can be filled two moves later, although the next token
+        // of Yellow scores lines on the way.
+        assert!(!board.is_dead());
+        for max_depth in 3..=5 {
+            let next_move = board
+                .search(&mut test_engine(), Symbol::Yellow, max_depth)
+                .unwrap();
+            assert_eq!(0, next_move.score, "max_depth={max_depth}");
+        }
+    }
+
+    #[test]
+    fn same_score_as_negamax() {
+        for columns in test_games() {
+            let mut board = Connect4::<5, 4>::new(3);
+            let mut symbol = Symbol::Red;
+            for column in columns.into_iter().take(8) {
+                let expected = negamax(&mut board, symbol, 0, 3).unwrap();
+                let actual = next_move_impl(
+                    &mut board,
+                    &mut test_engine(),
+                    symbol,
+                    0,
+                    3,
+                    -INFINITY,
+                    INFINITY,
+                )
+                .unwrap();
+                assert_eq!(expected.score, actual.score, "\n{board}");
+                match board.play_column(symbol, column % 5) {
+                    Some(play_column) if !play_column.outcome.is_over() => (),
+                    _ => break,
+                }
+                symbol = symbol.other();
+            }
+        }
+    }
+
+    #[test]
+    fn transposition_table() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for column in [3, 3, 2, 4] {
+            board.play_column(Symbol::Red, column);
+        }
+        let mut engine = test_engine();
+        let expected = next_move_impl(
+            &mut board,
+            &mut engine,
+            Symbol::Red,
+            0,
+            4,
+            -INFINITY,
+            INFINITY,
+        )
+        .unwrap();
+        assert!(engine.table.hits() > 0);
+        assert!(engine.table.hits() < engine.table.probes());
+
+        let probes = engine.table.probes();
+        let actual = next_move_impl(
+            &mut board,
+            &mut engine,
+            Symbol::Red,
+            0,
+            4,
+            -INFINITY,
+            INFINITY,
+        )
+        .unwrap();
+        assert_eq!(
+            (expected.column, expected.score),
+            (actual.column, actual.score)
+        );
+        assert_eq!(probes + 1, engine.table.probes());
+    }
+
+    #[test]
+    fn mirror() {
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut mirror = Connect4::<7, 6>::new(4);
+        for (column, symbol) in [(3, Symbol::Red), (2, Symbol::Yellow), (1, Symbol::Red)] {
+            board.play_column(symbol, column);
+            mirror.play_column(symbol, 6 - column);
+        }
+        let mut engine = test_engine();
+        let expected = next_move_impl(
+            &mut board,
+            &mut engine,
+            Symbol::Yellow,
+            0,
+            4,
+            -INFINITY,
+            INFINITY,
+        )
+        .unwrap();
+
+        // The mirror image is found in the transposition table.
+        let probes = engine.table.probes();
+        let actual = next_move_impl(
+            &mut mirror,
+            &mut engine,
+            Symbol::Yellow,
+            0,
+            4,
+            -INFINITY,
+            INFINITY,
+        )
+        .unwrap();
+        assert_eq!(
+            (6 - expected.column, expected.score),
+            (actual.column, actual.score)
+        );
+        assert_eq!(probes + 1, engine.table.probes());
+    }
+
+    #[test]
+    fn symmetric() {
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut engine = test_engine();
+        let next_move = board.search(&mut engine, Symbol::Red, 0).unwrap();
+        assert!(next_move.column <= 3);
+        // The columns up to the middle one.
+        assert_eq!(4, engine.stats.leaves);
+
+        board.play_column(Symbol::Red, 0);
+        engine.stats = Default::default();
+        board.search(&mut engine, Symbol::Yellow, 0).unwrap();
+        assert_eq!(7, engine.stats.leaves);
+    }
+
+    #[test]
+    fn dead() {
+        let mut board = Connect4::<4, 2>::new(3);
+        for (column, symbol) in [
+            (0, Symbol::Red),
+            (1, Symbol::Yellow),
+            (2, Symbol::Red),
+            (3, Symbol::Yellow),
+            (1, Symbol::Red),
+            (2, Symbol::Yellow),
+        ] {
+            board.play_column(symbol, column);
+        }
+        for bitboard in [true, false] {
+            let mut engine = test_engine();
+            engine.bitboard = bitboard;
+            let next_move = board.search(&mut engine, Symbol::Red, 4).unwrap();
+            // Both moves end the search.
+            assert_eq!(1, next_move.pv.len());
+            assert_eq!(1, engine.stats.nodes);
+            assert_eq!(2, engine.stats.leaves);
+        }
+    }
+
+    #[test]
+    fn bitboard() {
+        for columns in test_games() {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            for column in columns {
+                let mut bitboard = BitBoard::from_board(&board, symbol).unwrap();
+                let expected = next_move_impl(
+                    &mut board,
+                    &mut test_engine(),
+                    symbol,
+                    0,
+                    3,
+                    -INFINITY,
+                    INFINITY,
+                )
+                .unwrap();
+                let actual = next_move_impl(
+                    &mut bitboard,
+                    &mut test_engine(),
+                    symbol,
+                    0,
+                    3,
+                    -INFINITY,
+                    INFINITY,
+                )
+                .unwrap();
+                assert_eq!(
+                    (expected.column, expected.score),
+                    (actual.column, actual.score),
+                    "\n{board}"
+                );
+                if board.play_column(symbol, column).unwrap().outcome.is_over() {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+        }
+    }
+
+    #[test]
+    fn next_move_timed() {
+        let mut board = Connect4::<5, 4>::new(3);
+        let mut symbol = Symbol::Red;
+        for column in [0, 1, 2, 3, 4, 0, 1, 2, 3, 4] {
+            board.play_column(symbol, column);
+            symbol = symbol.other();
+        }
+        let expected = negamax(&mut board, Symbol::Red, 0, 9).unwrap();
+        let mut engine = test_engine();
+        // The search stops once it reaches the end of the game, long before the deadline.
+        let timed_move = board
+            .next_move_timed(&mut engine, Symbol::Red, Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(9, timed_move.depth);
+
+        assert_eq!(expected.score, timed_move.next_move.score);
+
+        // The search of the empty board stops at the deadline, and the engine is ready for
+        // searches without one.
+        let budget = Duration::from_millis(200);
+        let mut board = Connect4::<7, 6>::new(4);
+        let start = Instant::now();
+        let timed_move = board
+            .next_move_timed(&mut engine, Symbol::Red, budget)
+            .unwrap();
+        assert!(budget <= start.elapsed());
+        assert!(timed_move.depth < 41);
+        assert_eq!(timed_move.next_move.column, timed_move.next_move.pv[0]);
+        assert_eq!(None, engine.deadline);
+        assert!(!engine.stopped);
+    }
+
+    #[test]
+    fn next_move_timed_out() {
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut engine = test_engine();
+        let start = Instant::now();
+        let timed_move = board
+            .next_move_timed(&mut engine, Symbol::Red, Duration::from_millis(1))
+            .unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert!(timed_move.depth < 41);
+        assert!(!engine.stopped);
+        assert_eq!(None, engine.deadline);
+
+        let timed_move = board
+            .next_move_timed(&mut engine, Symbol::Yellow, Duration::ZERO)
+            .unwrap();
+        assert_eq!(0, timed_move.depth);
+    }
+
+    #[test]
+    fn book() {
+        let mut engine = test_engine();
+        engine.book = Some(Book::build::<5, 4>(&mut test_engine(), 3, 3, 4, |_| ()));
+        let mut board = Connect4::<5, 4>::new(3);
+        board.play_column(Symbol::Red, 4);
+        let book_move = engine
+            .book
+            .as_ref()
+            .unwrap()
+            .get(&board, Symbol::Yellow)
+            .unwrap();
+
+        let next_move = board
+            .next_move_with(&mut engine, Symbol::Yellow, 0)
+            .unwrap();
+        assert_eq!(
+            (book_move.column, book_move.score),
+            (next_move.column, next_move.score)
+        );
+        assert_eq!(0, engine.table.probes());
+
+        let timed_move = board
+            .next_move_timed(&mut engine, Symbol::Red, Duration::ZERO)
+            .unwrap();
+        assert_eq!(4, timed_move.depth);
+        assert_eq!(0, engine.table.probes());
+        // A shallower book doesn't replace a deeper search.
+        let new_board = || {
+            let mut board = Connect4::<5, 4>::new(3);
+            board.play_column(Symbol::Red, 4);
+            board
+        };
+        let expected = new_board()
+            .search(&mut test_engine(), Symbol::Yellow, 6)
+            .unwrap();
+        let next_move = new_board()
+            .next_move_with(&mut engine, Symbol::Yellow, 6)
+            .unwrap();
+        assert_eq!(
+            (expected.column, expected.score),
+            (next_move.column, next_move.score)
+        );
+        assert!(engine.table.probes() > 0);
+
+        let timed_move = new_board()
+            .next_move_timed(&mut engine, Symbol::Yellow, Duration::from_secs(1))
+            .unwrap();
+        assert!(timed_move.depth > 4);
+    }
+
+    #[test]
+    fn move_ordering() {
+        use Heuristic::*;
+        let nodes = |heuristics: &[Heuristic]| {
+            let mut nodes = 0;
+            let mut scores = vec![];
+            for columns in test_games() {
+                let mut board = Connect4::<7, 6>::new(4);
+                let mut symbol = Symbol::Red;
+                for column in columns.into_iter().take(10) {
+                    let mut engine = test_engine();
+                    engine.ordering = MoveOrdering::new(heuristics.to_vec());
+                    scores.push(board.search(&mut engine, symbol, 4).unwrap().score);
+                    nodes += engine.stats.nodes;
+                    if board.play_column(symbol, column).unwrap().outcome.is_over() {
+                        break;
+                    }
+                    symbol = symbol.other();
+                }
+            }
+            println!("{heuristics:?}: {nodes} nodes");
+            (nodes, scores)
+        };
+        let (left_to_right, expected) = nodes(&[]);
+        for heuristics in [
+            &[Center][..],
+            &[Score],
+            &[Score, History],
+            &[Score, Killers],
+            &[TableMove, Score],
+        ] {
+            let (nodes, scores) = nodes(heuristics);
+            assert_eq!(expected, scores);
+            assert!(nodes < left_to_right);
+        }
+        let (all, scores) = nodes(&MoveOrdering::default().heuristics);
+        assert_eq!(expected, scores);
+        assert!(all < nodes(&[TableMove, Score]).0);
+    }
+
+    #[test]
+    fn threads() {
+        // Positions searched by the main thread, on one thread and on 4 threads.
+        let mut main_nodes = [0, 0];
+        for columns in test_games() {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            for column in columns.into_iter().take(6) {
+                // On one thread, the search of the bitboard before Lazy SMP.
+                let mut engine = test_engine();
+                engine.ordering.new_search(7, 6);
+                engine.search_depth = 5;
+                let mut position = BitBoard::from_board(&board, symbol).unwrap();
+                let next_move = next_move_impl(
+                    &mut position,
+                    &mut engine,
+                    symbol,
+                    0,
+                    5,
+                    -INFINITY,
+                    INFINITY,
+                )
+                .unwrap();
+                let mut search = |threads| {
+                    let mut engine = test_engine();
+                    engine.threads = threads;
+                    let next_move = board.search(&mut engine, symbol, 5).unwrap();
+                    (
+                        next_move.column,
+                        next_move.score,
+                        engine.stats.nodes - engine.stats.helper_nodes,
+                    )
+                };
+                let single = search(1);
+                assert_eq!(
+                    (next_move.column, next_move.score, engine.stats.nodes),
+                    single
+                );
+
+                // The helpers share results of the same depth, which don't change the score.
+                let (parallel_column, parallel_score, parallel_nodes) = search(4);
+                assert_eq!(single.1, parallel_score, "\n{board}");
+                main_nodes[0] += single.2;
+                main_nodes[1] += parallel_nodes;
+                let play_column = board.play_column(symbol, parallel_column).unwrap();
+                play_column.undo(&mut board);
+
+                board.play_column(symbol, column);
+                symbol = symbol.other();
+            }
+        }
+        assert!(main_nodes[1] < main_nodes[0], "{main_nodes:?}");
+    }
+
+    #[test]
+    fn principal_variation() {
+        for columns in test_games() {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            for column in columns {
+                for bitboard in [true, false] {
+                    let mut engine = test_engine();
+                    engine.bitboard = bitboard;
+                    let next_move = board.search(&mut engine, symbol, 4).unwrap();
+                    assert_eq!(next_move.column, next_move.pv[0]);
+
+                    // The variation goes as deep as the search, or to the end of the game, and
+                    // playing it scores the move.
+                    let mut score = 0;
+                    let mut played = vec![];
+                    let mut pv_symbol = symbol;
+                    for (i, column) in next_move.pv.iter().enumerate() {
+                        let play_column = board.play_column(pv_symbol, *column).unwrap();
+                        score = play_column.delta_score - score;
+                        assert!(!play_column.outcome.is_over() || i == next_move.pv.len() - 1);
+                        played.push(play_column);
+                        pv_symbol = pv_symbol.other();
+                    }
+                    let ends = played.last().unwrap().outcome.is_over() || board.is_dead();
+                    for play_column in played.iter().rev() {
+                        play_column.undo(&mut board);
+                    }
+                    assert!(
+                        next_move.pv.len() == 5 || ends,
+                        "{:?}\n{board}",
+                        next_move.pv
+                    );
+                    let sign = if next_move.pv.len() % 2 == 1 { 1 } else { -1 };
+                    assert_eq!(next_move.score, sign * score, "{:?}\n{board}", next_move.pv);
+                }
+
+                if board.play_column(symbol, column).unwrap().outcome.is_over() {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+        }
+    }
+
+    #[test]
+    fn stats() {
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut engine = test_engine();
+        board.next_move_with(&mut engine, Symbol::Red, 4).unwrap();
+        let stats = engine.stats.clone();
+        println!("{stats}");
+        assert_eq!(5, stats.depth);
+        assert!(stats.leaves > stats.nodes);
+        assert!(0 < stats.cutoffs && stats.cutoffs < stats.nodes);
+        assert!(!stats.elapsed.is_zero());
+        let branching_factor = stats.effective_branching_factor();
+        assert!(1. < branching_factor && branching_factor < 7.);
+
+        board
+            .next_move_with(&mut engine, Symbol::Yellow, 2)
+            .unwrap();
+        assert!(engine.stats.depth <= 3);
+        assert!(engine.stats.nodes < stats.nodes);
+    }
+
+    #[test]
+    fn eval_params() {
+        let params = EvalParams {
+            token_weight: 7,
+            empty_weight: 3,
+        };
+        for columns in test_games() {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            for column in columns {
+                let mut engine = test_engine();
+                engine.params = params;
+                let expected = board.search(&mut engine, symbol, 3).unwrap();
+                assert_eq!(EvalParams::default(), board.params());
+                engine = test_engine();
+                engine.params = params;
+                engine.bitboard = false;
+                let actual = board.search(&mut engine, symbol, 3).unwrap();
+                assert_eq!(
+                    (expected.column, expected.score),
+                    (actual.column, actual.score),
+                    "\n{board}"
+                );
+                if board.play_column(symbol, column).unwrap().outcome.is_over() {
+                    break;
+                }
+                symbol = symbol.other();
+            }
+        }
+        let mut board = Connect4::<7, 6>::new(4);
+        let default = board.search(&mut test_engine(), Symbol::Red, 1).unwrap();
+        let mut engine = test_engine();
+        engine.params = params;
+        assert_ne!(
+            default.score,
+            board.search(&mut engine, Symbol::Red, 1).unwrap().score
+        );
+    }
+
+    fn test_engine() -> Engine {
+        Engine::new(1 << 16)
+    }
+
+    fn test_games() -> Vec<Vec<i32>> {
+        vec![
+            vec![3, 3, 2, 4, 4, 2, 1, 5, 5, 0],
+            vec![0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3],
+            vec![3, 2, 3, 2, 4, 4, 6, 1, 1, 0, 5],
+            vec![6, 6, 6, 6, 5, 0, 1, 2, 3, 3, 4],
+        ]
+    }
+
+    /// The search `next_move_impl` implemented before alpha-beta pruning.
+    ///
+    /// It searched the opponent from `score - delta_score` and scored the move
+    /// `score - opponent_play.score`, so from depth 2 on the change of the score of a move
+    /// cancels out with the score it was searched from. Its scores only agree with the
+    /// alpha-beta search up to depth 1.
+    fn baseline(
+        board: &mut Connect4<7, 6>,
+        symbol: Symbol,
+        score: i32,
+        max_depth: i32,
+    ) -> Option<Move> {
+        let mut best_move: Option<Move> = None;
+        for column in 0..board.width() {
+            if let Some(play_column) = board.play_column(symbol, column) {
+                if play_column.outcome.is_over() || max_depth == 0 {
+                    update_best_move(
+                        &mut best_move,
+                        column,
+                        score + play_column.delta_score,
+                        play_column.outcome,
+                    );
+                } else if let Some(opponent_play) = baseline(
+                    board,
+                    symbol.other(),
+                    score - play_column.delta_score,
+                    max_depth - 1,
+                ) {
+                    update_best_move(
+                        &mut best_move,
+                        column,
+                        score - opponent_play.score,
+                        play_column.outcome,
+                    );
+                }
+                play_column.undo(board);
+            };
+        }
+        best_move
+    }
+
+    /// Minimax search without pruning, scored like the alpha-beta search: the opponent is
+    /// searched from the opposite of the score after the move, so that the changes of the
+    /// score of all the moves add up, at any depth.
+    fn minimax(
+        board: &mut Connect4<7, 6>,
+        symbol: Symbol,
+        score: i32,
+        max_depth: i32,
+        nodes: &mut u64,
+    ) -> Option<Move> {
+        *nodes += 1;
+        let mut best_move: Option<Move> = None;
+        for column in 0..board.width() {
+            if let Some(play_column) = board.play_column(symbol, column) {
+                if play_column.outcome.is_over() || max_depth == 0 {
+                    update_best_move(
+                        &mut best_move,
+                        column,
+                        score + play_column.delta_score,
+                        play_column.outcome,
+                    );
+                } else if let Some(opponent_play) = minimax(
+                    board,
+                    symbol.other(),
+                    -(score + play_column.delta_score),
+                    max_depth - 1,
+                    nodes,
+                ) {
+                    update_best_move(
+                        &mut best_move,
+                        column,
+                        -opponent_play.score,
+                        play_column.outcome,
+                    );
+                }
+                play_column.undo(board);
+            };
+        }
+        best_move
+    }
+
+    /// Negamax search without pruning.
+    fn negamax(
+        board: &mut Connect4<5, 4>,
+        symbol: Symbol,
+        score: i32,
+        max_depth: i32,
+    ) -> Option<Move> {
+        let mut best_move: Option<Move> = None;
+        for column in 0..board.width() {
+            if let Some(play_column) = board.play_column(symbol, column) {
+                let score = score + play_column.delta_score;
+                let move_score = if play_column.outcome.is_over() || max_depth == 0 {
+                    score
+                } else {
+                    negamax(board, symbol.other(), -score, max_depth - 1)
+                        .map_or(score, |opponent_move| -opponent_move.score)
+                };
+                play_column.undo(board);
+                update_best_move(&mut best_move, column, move_score, play_column.outcome);
+            };
+        }
+        best_move
+    }
 }
@@ -0,0 +1,266 @@
+use crate::shared::board::board_all_points::BoardAllPoints;
+use crate::shared::board::board_size::BoardSize;
+use crate::shared::board::board_size::Size;
+use crate::shared::coord::point::Point;
+
+use super::board::Connect4;
+use super::eval::EvalParams;
+use super::game::intersecting_lines;
+use super::symbol::Symbol;
+
+/// Number of tokens of each player in each line of `winning_tokens` cells of a board.
+///
+/// A line scores according to `params` for the only player with tokens in it, or for both
+/// players when it's empty.
+pub(super) struct Tally {
+    width: i32,
+    winning_tokens: usize,
+    params: EvalParams,
+
+    /// Score of a line by number of tokens of its only player.
+    values: Vec<i64>,
+
+    /// Lines going through each cell, row by row.
+    cell_lines: Vec<Vec<u32>>,
+
+    /// First and last cells of each line.
+    ends: Vec<[Point; 2]>,
+
+    /// Tokens of Red and Yellow in each line.
+    counts: Vec<[u8; 2]>,
+
+    /// Number of lines without tokens of both players, that one of them can still fill.
+    open_lines: usize,
+
+    /// Sum of the scores of the lines of Red minus those of Yellow.
+    score: i64,
+}
+
+impl Tally {
+    /// Tally of the empty board of `size`.
+    pub(super) fn new(size: Size, winning_tokens: i32, params: EvalParams) -> Self {
+        let mut cell_lines = vec![vec![]; (size.width() * size.height()) as usize];
+        let mut ends = vec![];
+        let mut lines = 0;
+        if winning_tokens > 0 {
+            for position in size.all_points() {
+                for line in intersecting_lines(&size, position, winning_tokens) {
+                    let line: Vec<Point> = line.collect();
+                    // Each line is counted once, from its first cell.
+                    if line[0] == position {
+                        ends.push([line[0], line[line.len() - 1]]);
+                        for point in line {
+                            cell_lines[(point.y * size.width() + point.x) as usize].push(lines);
+                        }
+                        lines += 1;
+                    }
+                }
+            }
+        }
+        let winning_tokens = winning_tokens.max(0);
+        Self {
+            width: size.width(),
+            winning_tokens: winning_tokens as usize,
+            params,
+            values: (0..=winning_tokens)
+                .map(|tokens| params.line_score(winning_tokens, tokens))
+                .collect(),
+            cell_lines,
+            ends,
+            counts: vec![[0; 2]; lines as usize],
+            open_lines: lines as usize,
+            score: 0,
+        }
+    }
+
+    /// Counts a token of `symbol` dropped at `position`.
+    ///
+    /// Returns the sum of the scores of the lines going through `position` for `symbol`, and
+    /// the first and last cells of one of them that is full, if any.
+    pub(super) fn play(&mut self, position: Point, symbol: Symbol) -> (i64, Option<[Point; 2]>) {
+        let (player, other) = Self::players(symbol);
+        let mut delta_score = 0;
+        let mut full_line = None;
+        let cell = (position.y * self.width + position.x) as usize;
+        for line in &self.cell_lines[cell] {
+            let counts = &mut self.counts[*line as usize];
+            self.score -= Self::line_score(&self.values, counts);
+            counts[player] += 1;
+            self.score += Self::line_score(&self.values, counts);
+            if counts[player] == 1 && counts[other] != 0 {
+                self.open_lines -= 1;
+            }
+            if counts[other] == 0 {
+                delta_score += self.values[counts[player] as usize];
+                if counts[player] as usize == self.winning_tokens {
+                    full_line = Some(self.ends[*line as usize]);
+                }
+            }
+        }
+        (delta_score, full_line)
+    }
+
+    /// Forgets the token of `symbol` counted at `position` by `play`.
+    pub(super) fn undo(&mut self, position: Point, symbol: Symbol) {
+        let (player, other) = Self::players(symbol);
+        let cell = (position.y * self.width + position.x) as usize;
+        for line in &self.cell_lines[cell] {
+            let counts = &mut self.counts[*line as usize];
+            self.score -= Self::line_score(&self.values, counts);
+            counts[player] -= 1;
+            self.score += Self::line_score(&self.values, counts);
+            if counts[player] == 0 && counts[other] != 0 {
+                self.open_lines += 1;
+            }
+        }
+    }
+
+    /// Whether every line has tokens of both players.
+    pub(super) fn is_dead(&self) -> bool {
+        self.open_lines == 0
+    }
+
+    /// Scores the lines with `params` from now on.
+    pub(super) fn set_params(&mut self, params: EvalParams) {
+        self.params = params;
+        for (tokens, value) in self.values.iter_mut().enumerate() {
+            *value = params.line_score(self.winning_tokens as i32, tokens as i32);
+        }
+        self.score = self
+            .counts
+            .iter()
+            .map(|counts| Self::line_score(&self.values, counts))
+            .sum();
+    }
+
+    /// Score of the board for `symbol`.
+    pub(super) fn score(&self, symbol: Symbol) -> i64 {
+        match symbol {
+            Symbol::Yellow => -self.score,
+            _ => self.score,
+        }
+    }
+
+    /// Score of a line with `counts` tokens, for Red.
+    fn line_score(values: &[i64], counts: &[u8; 2]) -> i64 {
+        match counts {
+            [0, 0] => 0,
+            [red, 0] => values[*red as usize],
+            [0, yellow] => -values[*yellow as usize],
+            _ => 0,
+        }
+    }
+
+    fn players(symbol: Symbol) -> (usize, usize) {
+        match symbol {
+            Symbol::Yellow => (1, 0),
+            _ => (0, 1),
+        }
+    }
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// Score of the whole board for `symbol`, the sum of the scores of its lines minus those
+    /// of the other player.
+    pub fn evaluate(&self, symbol: Symbol) -> i64 {
+        self.tally.score(symbol)
+    }
+
+    pub fn params(&self) -> EvalParams {
+        self.tally.params
+    }
+
+    /// Scores the board with `params` from now on.
+    pub fn set_params(&mut self, params: EvalParams) {
+        self.tally.set_params(params);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::connect4::position::Position;
+    use crate::shared::board::board_get_set::BoardGet;
+    use crate::shared::game_outcome::GameOutcome;
+
+    use super::*;
+
+    #[test]
+    fn same_as_rescan() {
+        for columns in [
+            vec![3, 3, 2, 4, 4, 2, 1, 5, 5, 0, 1, 0, 0],
+            vec![0, 1, 2, 3, 4, 5, 6, 6, 5, 4, 3, 2, 1, 0, 0, 0, 6, 6],
+            vec![6, 6, 6, 6, 5, 0, 1, 2, 3, 3, 4, 3, 3, 4, 4, 5, 5],
+        ] {
+            let mut board = Connect4::<7, 6>::new(4);
+            let mut symbol = Symbol::Red;
+            let mut played = vec![];
+            for column in columns {
+                let Some(play_column) = board.play_column(symbol, column) else {
+                    continue;
+                };
+                let position = play_column.position;
+                let rescan = board.eval_position(symbol, position);
+                assert_eq!(rescan.score, play_column.delta_score);
+                assert_eq!(rescan.end_of_game, play_column.outcome.is_over());
+                assert_rescan(&board);
+                played.push(play_column);
+                symbol = symbol.other();
+            }
+            for play_column in played.iter().rev() {
+                play_column.undo(&mut board);
+                assert_rescan(&board);
+            }
+            assert_eq!(0, board.evaluate(Symbol::Red));
+        }
+    }
+
+    #[test]
+    fn outcome() {
+        let mut board = Connect4::<5, 4>::new(3);
+        for column in [0, 1] {
+            let play_column = board.play_column(Symbol::Yellow, column).unwrap();
+            assert_eq!(GameOutcome::Ongoing, play_column.outcome);
+        }
+        let play_column = board.play_column(Symbol::Yellow, 2).unwrap();
+        assert_eq!(
+            GameOutcome::Win {
+                winner: Symbol::Yellow,
+                line: [Point::new(0, 3), Point::new(2, 3)],
+            },
+            play_column.outcome
+        );
+        play_column.undo(&mut board);
+        // Yellow has 1 line of 2 tokens, and 5 of 1 token.
+        assert_eq!(100_000 + 5 * 10_000, board.evaluate(Symbol::Yellow));
+    }
+
+    /// Checks the tally against all the lines of `board`.
+    fn assert_rescan(board: &Connect4<7, 6>) {
+        let mut lines = HashSet::new();
+        for position in board.all_points() {
+            for line in intersecting_lines(board, position, board.winning_tokens) {
+                lines.insert(line.map(|point| (point.x, point.y)).collect::<Vec<_>>());
+            }
+        }
+        let mut score = 0;
+        for line in lines {
+            let tokens = |symbol| {
+                line.iter()
+                    .filter(|(x, y)| board.get(&Point::new(*x, *y)).symbol == symbol)
+                    .count() as u32
+            };
+            let (red, yellow) = (tokens(Symbol::Red), tokens(Symbol::Yellow));
+            let line_score = |tokens| 100i64.pow(tokens) * 10i64.pow(4 - tokens);
+            if yellow == 0 {
+                score += line_score(red);
+            }
+            if red == 0 {
+                score -= line_score(yellow);
+            }
+        }
+        assert_eq!(score, board.evaluate(Symbol::Red), "\n{board}");
+        assert_eq!(-score, board.evaluate(Symbol::Yellow));
+    }
+}
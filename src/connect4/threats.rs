@@ -0,0 +1,175 @@
+use crate::shared::board::array_board::ArrayBoard;
+use crate::shared::board::board_all_points::BoardAllPoints;
+use crate::shared::board::board_generate::BoardGenerate;
+use crate::shared::board::board_get_set::BoardGet;
+use crate::shared::board::board_size::BoardSize;
+use crate::shared::coord::point::Point;
+
+use super::board::Connect4;
+use super::game::intersecting_lines;
+use super::symbol::Symbol;
+
+/// Empty cell that would complete a line of `winning_tokens` for a player.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Threat {
+    pub position: Point,
+    pub kind: ThreatKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThreatKind {
+    /// The cell can be played now.
+    Immediate,
+
+    /// The cell can't be played until the cells below it are.
+    Latent(Parity),
+}
+
+/// Parity of the row of a cell, counting rows from 1 at the bottom of the board.
+///
+/// Following Allis' rules of zugzwang, the player who moves first can hope to fill the board
+/// up to an odd threat, and the other player up to an even threat.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Parity {
+    Odd,
+    Even,
+}
+
+impl<const WIDTH: usize, const HEIGHT: usize> Connect4<WIDTH, HEIGHT> {
+    /// The threats of `symbol`, column by column from the left, and from top to bottom in
+    /// each column.
+    pub fn threats(&self, symbol: Symbol) -> Vec<Threat> {
+        self.all_points()
+            .filter(|point| self.get(point).symbol == Symbol::Empty)
+            .filter(|point| {
+                intersecting_lines(self, *point, self.winning_tokens).any(|mut line| {
+                    line.all(|position| position == *point || self.get(&position).symbol == symbol)
+                })
+            })
+            .map(|position| Threat {
+                position,
+                kind: if self.is_playable(position) {
+                    ThreatKind::Immediate
+                } else if (self.height() - position.y) % 2 == 1 {
+                    ThreatKind::Latent(Parity::Odd)
+                } else {
+                    ThreatKind::Latent(Parity::Even)
+                },
+            })
+            .collect()
+    }
+
+    /// The board with the threats of `symbol` drawn over the empty cells: `!` for the
+    /// immediate threats, `1` and `2` for the latent threats on odd and even rows.
+    pub fn view_threats(&self, symbol: Symbol) -> ArrayBoard<WIDTH, HEIGHT, char> {
+        let threats = self.threats(symbol);
+        ArrayBoard::generate(|point| {
+            let threat = threats.iter().find(|threat| threat.position == *point);
+            match threat.map(|threat| threat.kind) {
+                Some(ThreatKind::Immediate) => '!',
+                Some(ThreatKind::Latent(Parity::Odd)) => '1',
+                Some(ThreatKind::Latent(Parity::Even)) => '2',
+                None => *self.get(point).as_xo(),
+            }
+        })
+    }
+
+    /// Whether a token dropped in the column of the empty cell `position` lands there.
+    fn is_playable(&self, position: Point) -> bool {
+        let below = Point::new(position.x, position.y + 1);
+        !self.is_valid(&below) || self.get(&below).symbol != Symbol::Empty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::connect4::position::Position;
+
+    use super::*;
+
+    #[test]
+    fn threats() {
+        let mut board = Connect4::<7, 6>::new(4);
+        for (symbol, columns) in [
+            (Symbol::Red, [0, 1, 2].as_slice()),
+            (Symbol::Yellow, &[4, 4, 4, 5, 5, 6]),
+            (Symbol::Red, &[4, 5, 6, 6]),
+            (Symbol::Yellow, &[6]),
+        ] {
+            for column in columns {
+                board.play_column(symbol, *column);
+            }
+        }
+        println!("{}", board.view_threats(Symbol::Red));
+        assert_eq!(
+            r#"
+    | 1 | 2 | 3 | 4 | 5 | 6 | 7 
+----+---+---+---+---+---+---+---
+ A  |   |   |   |   |   |   |   
+----+---+---+---+---+---+---+---
+ B  |   |   |   | 1 |   |   |   
+----+---+---+---+---+---+---+---
+ C  |   |   |   |   | X |   | O 
+----+---+---+---+---+---+---+---
+ D  |   |   |   |   | O | X | X 
+----+---+---+---+---+---+---+---
+ E  |   |   |   |   | O | O | X 
+----+---+---+---+---+---+---+---
+ F  | X | X | X | ! | O | O | O 
+"#,
+            format!("\n{}", board.view_threats(Symbol::Red))
+        );
+        assert_eq!(
+            vec![
+                Threat {
+                    position: Point::new(3, 1),
+                    kind: ThreatKind::Latent(Parity::Odd)
+                },
+                Threat {
+                    position: Point::new(3, 5),
+                    kind: ThreatKind::Immediate
+                },
+            ],
+            board.threats(Symbol::Red)
+        );
+        assert_eq!(
+            vec![
+                Threat {
+                    position: Point::new(3, 2),
+                    kind: ThreatKind::Latent(Parity::Even)
+                },
+                Threat {
+                    position: Point::new(3, 5),
+                    kind: ThreatKind::Immediate
+                },
+            ],
+            board.threats(Symbol::Yellow)
+        );
+    }
+
+    #[test]
+    fn parity() {
+        let mut board = Connect4::<5, 4>::new(3);
+        for column in [0, 1, 1] {
+            board.play_column(Symbol::Yellow, column);
+        }
+        board.play_column(Symbol::Red, 2);
+        board.play_column(Symbol::Red, 2);
+        board.play_column(Symbol::Red, 0);
+        board.play_column(Symbol::Red, 1);
+        println!("{}", board.view_threats(Symbol::Red));
+        assert_eq!(
+            vec![
+                (Point::new(0, 0), ThreatKind::Latent(Parity::Even)),
+                (Point::new(2, 0), ThreatKind::Latent(Parity::Even)),
+                (Point::new(2, 1), ThreatKind::Immediate),
+                (Point::new(3, 3), ThreatKind::Immediate),
+            ],
+            board
+                .threats(Symbol::Red)
+                .into_iter()
+                .map(|threat| (threat.position, threat.kind))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+/// Fixed-size hash table of the positions already searched.
+///
+/// Each position is stored in a single slot chosen by its hash. Entries of deeper searches
+/// are kept when a shallower search of a different position collides with them.
+///
+/// The table can be shared by threads without locks: a slot holds the entry packed in a
+/// `u64` and the hash xor-ed with it, so that an entry torn by concurrent writes doesn't
+/// match its hash and is ignored.
+pub struct TranspositionTable {
+    slots: Vec<Slot>,
+    probes: AtomicU64,
+    hits: AtomicU64,
+}
+
+#[derive(Default)]
+struct Slot {
+    /// The hash of the position xor the data.
+    check: AtomicU64,
+
+    /// The entry, without its hash.
+    data: AtomicU64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Entry {
+    hash: u64,
+
+    /// Score of the position, relative to the score of the board when it was reached.
+    pub score: i32,
+
+    /// How many more moves were searched from the position.
+    pub depth: u8,
+
+    /// Column of the best move found.
+    pub column: u8,
+
+    /// Whether `score` is exact or only a bound of the actual score.
+    pub bound: Bound,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bound {
+    #[default]
+    Exact,
+    /// The search failed high: the actual score is at least `score`.
+    Lower,
+    /// The search failed low: the actual score is at most `score`.
+    Upper,
+}
+
+impl Entry {
+    pub fn new(score: i32, depth: u8, column: u8, bound: Bound) -> Self {
+        Self {
+            hash: 0,
+            score,
+            depth,
+            column,
+            bound,
+        }
+    }
+
+    fn pack(&self) -> u64 {
+        let bound = match self.bound {
+            Bound::Exact => 0,
+            Bound::Lower => 1,
+            Bound::Upper => 2,
+        };
+        self.score as u32 as u64
+            | (self.depth as u64) << 32
+            | (self.column as u64) << 40
+            | bound << 48
+    }
+
+    fn unpack(hash: u64, data: u64) -> Self {
+        Self {
+            hash,
+            score: data as u32 as i32,
+            depth: (data >> 32) as u8,
+            column: (data >> 40) as u8,
+            bound: match (data >> 48) as u8 {
+                1 => Bound::Lower,
+                2 => Bound::Upper,
+                _ => Bound::Exact,
+            },
+        }
+    }
+}
+
+impl TranspositionTable {
+    /// Creates a table using at most `memory` bytes.
+    pub fn with_memory(memory: usize) -> Self {
+        let len = (memory / std::mem::size_of::<Slot>()).max(1);
+        Self {
+            slots: (0..len).map(|_| Slot::default()).collect(),
+            probes: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, hash: u64) -> Option<Entry> {
+        self.probes.fetch_add(1, Ordering::Relaxed);
+        let entry = self.load(hash);
+        if entry.hash == hash && entry.depth > 0 {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    pub fn set(&self, hash: u64, entry: Entry) {
+        let slot = self.load(hash);
+        if slot.hash == hash || slot.depth <= entry.depth {
+            let data = entry.pack();
+            let slot = &self.slots[self.index(hash)];
+            slot.check.store(hash ^ data, Ordering::Relaxed);
+            slot.data.store(data, Ordering::Relaxed);
+        }
+    }
+
+    /// Forgets all the positions.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            *slot = Slot::default();
+        }
+    }
+
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn probes(&self) -> u64 {
+        self.probes.load(Ordering::Relaxed)
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        let probes = self.probes();
+        if probes == 0 {
+            0.
+        } else {
+            self.hits() as f64 / probes as f64
+        }
+    }
+
+    /// The entry of the slot of `hash`, with the hash of the position stored in the slot.
+    fn load(&self, hash: u64) -> Entry {
+        let slot = &self.slots[self.index(hash)];
+        let data = slot.data.load(Ordering::Relaxed);
+        let check = slot.check.load(Ordering::Relaxed);
+        Entry::unpack(check ^ data, data)
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        (hash % self.slots.len() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(score: i32, depth: u8) -> Entry {
+        Entry::new(score, depth, 3, Bound::Lower)
+    }
+
+    #[test]
+    fn with_memory() {
+        assert_eq!(16, std::mem::size_of::<Slot>());
+        assert_eq!(1 << 16, TranspositionTable::with_memory(1 << 20).len());
+        assert_eq!(1, TranspositionTable::with_memory(0).len());
+    }
+
+    #[test]
+    fn get_set() {
+        let table = TranspositionTable::with_memory(1 << 10);
+        assert_eq!(None, table.get(123));
+        table.set(123, entry(45, 2));
+        assert_eq!(
+            Some(Entry {
+                hash: 123,
+                ..entry(45, 2)
+            }),
+            table.get(123)
+        );
+        assert_eq!(None, table.get(123 + table.len() as u64));
+        assert_eq!((3, 1), (table.probes(), table.hits()));
+        assert_eq!(1. / 3., table.hit_rate());
+    }
+
+    #[test]
+    fn keeps_deeper_entries() {
+        let table = TranspositionTable::with_memory(1 << 10);
+        let other = 123 + table.len() as u64;
+        table.set(123, entry(1, 5));
+        table.set(other, entry(2, 4));
+        assert_eq!(Some(1), table.get(123).map(|entry| entry.score));
+
+        table.set(other, entry(3, 5));
+        assert_eq!(None, table.get(123));
+        assert_eq!(Some(3), table.get(other).map(|entry| entry.score));
+
+        table.set(other, entry(4, 1));
+        assert_eq!(Some(4), table.get(other).map(|entry| entry.score));
+    }
+
+    #[test]
+    fn shared() {
+        let table = TranspositionTable::with_memory(1 << 10);
+        let len = table.len() as u64;
+        std::thread::scope(|scope| {
+            for thread in 1..=4 {
+                let table = &table;
+                scope.spawn(move || {
+                    for i in 0..100_000 {
+                        // All the threads write different entries to the same slot.
+                        let hash = 1 + len * (i % 3);
+                        if let Some(entry) = table.get(hash) {
+                            assert_eq!(hash, entry.score as u64 % 1000);
+                        }
+                        let score = (hash + 1000 * thread) as i32;
+                        table.set(hash, entry(score, (i % 8 + 1) as u8));
+                    }
+                });
+            }
+        });
+        assert_eq!(400_000, table.probes());
+    }
+}
@@ -0,0 +1,284 @@
+use crate::shared::game_outcome::GameOutcome;
+
+use super::board::Connect4;
+use super::engine::Engine;
+use super::eval::EvalParams;
+use super::position::Position;
+use super::strategy::Move;
+use super::symbol::Symbol;
+
+/// Memory of the transposition table of each player of a game.
+const TABLE_MEMORY: usize = 1 << 16;
+
+/// Bounds of the weights tried.
+const MIN_WEIGHT: i32 = 1;
+const MAX_WEIGHT: i32 = 1000;
+
+/// Hill-climbing of the weights of the evaluation, by self-play.
+///
+/// Each round plays the current weights against their neighbours, each weight scaled up or
+/// down by `step`, and keeps the first neighbour that wins the match. When none does, the
+/// step is halved.
+pub struct Tuner {
+    /// How many moves ahead the players search.
+    pub depth: i32,
+
+    /// Relative change of a weight between neighbours.
+    pub step: f64,
+
+    pub winning_tokens: i32,
+}
+
+/// Result of a round of `Tuner::tune`.
+#[derive(Debug)]
+pub struct Round {
+    pub params: EvalParams,
+
+    /// Share of the points won by `params` against the previous weights, if they changed.
+    pub score: Option<f64>,
+}
+
+impl Tuner {
+    /// Tuner of the weights of boards of `winning_tokens`, playing `depth` moves ahead.
+    pub fn new(winning_tokens: i32, depth: i32) -> Self {
+        Self {
+            depth,
+            step: 0.5,
+            winning_tokens,
+        }
+    }
+
+    /// Runs `rounds` rounds of hill-climbing from `params`, and returns the best weights.
+    ///
+    /// `progress` is called after each round.
+    pub fn tune<const WIDTH: usize, const HEIGHT: usize>(
+        &mut self,
+        mut params: EvalParams,
+        rounds: usize,
+        mut progress: impl FnMut(&Round),
+    ) -> EvalParams {
+        for _ in 0..rounds {
+            let winner = self
+                .neighbours(params)
+                .into_iter()
+                .map(|candidate| {
+                    (
+                        candidate,
+                        self.play_match::<WIDTH, HEIGHT>(candidate, params),
+                    )
+                })
+                .find(|(_, score)| *score > 0.5);
+            let round = match winner {
+                Some((candidate, score)) => {
+                    params = candidate;
+                    Round {
+                        params,
+                        score: Some(score),
+                    }
+                }
+                None => {
+                    self.step /= 2.;
+                    Round {
+                        params,
+                        score: None,
+                    }
+                }
+            };
+            progress(&round);
+        }
+        params
+    }
+
+    /// Share of the points won by `candidate` against `incumbent`, a draw counting as half a
+    /// win.
+    ///
+    /// The players play every opening of two moves twice, once with each color.
+    pub fn play_match<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        candidate: EvalParams,
+        incumbent: EvalParams,
+    ) -> f64 {
+        self.play_engines::<WIDTH, HEIGHT>(
+            |engine| engine.params = candidate,
+            |engine| engine.params = incumbent,
+        )
+    }
+
+    /// Share of the points won by the engines set up by `candidate` against those set up by
+    /// `incumbent`, like `play_match`.
+    ///
+    /// Each game starts with new engines, so that its moves don't depend on the previous
+    /// games.
+    pub fn play_engines<const WIDTH: usize, const HEIGHT: usize>(
+        &self,
+        candidate: impl Fn(&mut Engine),
+        incumbent: impl Fn(&mut Engine),
+    ) -> f64 {
+        let depth = self.depth;
+        play_players::<WIDTH, HEIGHT>(self.winning_tokens, |_| {
+            let set_ups: [&dyn Fn(&mut Engine); 2] = [&candidate, &incumbent];
+            set_ups.map(|set_up| {
+                let mut engine = Engine::new(TABLE_MEMORY);
+                set_up(&mut engine);
+                Box::new(move |board: &mut Connect4<WIDTH, HEIGHT>, symbol| {
+                    board.next_move_with(&mut engine, symbol, depth)
+                }) as Player<WIDTH, HEIGHT>
+            })
+        })
+    }
+
+    /// Weights with one of the weights of `params` scaled up or down by `step`.
+    fn neighbours(&self, params: EvalParams) -> Vec<EvalParams> {
+        let scale = |weight: i32, factor: f64| {
+            ((weight as f64 * factor).round() as i32).clamp(MIN_WEIGHT, MAX_WEIGHT)
+        };
+        let mut neighbours = vec![];
+        for factor in [1. + self.step, 1. / (1. + self.step)] {
+            neighbours.push(EvalParams {
+                token_weight: scale(params.token_weight, factor),
+                ..params
+            });
+            neighbours.push(EvalParams {
+                empty_weight: scale(params.empty_weight, factor),
+                ..params
+            });
+        }
+        neighbours.retain(|neighbour| *neighbour != params);
+        neighbours
+    }
+}
+
+/// Player of the games of a match, which plays its move on the board and returns it.
+pub(super) type Player<'p, const WIDTH: usize, const HEIGHT: usize> =
+    Box<dyn FnMut(&mut Connect4<WIDTH, HEIGHT>, Symbol) -> Option<Move> + 'p>;
+
+/// Share of the points won by a candidate against an incumbent, a draw counting as half a
+/// win.
+///
+/// The players play every opening of two moves twice, once with each color. `players`
+/// returns the candidate and the incumbent of each game, given its number, so that each
+/// game can start with new players.
+pub(super) fn play_players<'p, const WIDTH: usize, const HEIGHT: usize>(
+    winning_tokens: i32,
+    mut players: impl FnMut(u64) -> [Player<'p, WIDTH, HEIGHT>; 2],
+) -> f64 {
+    let mut points = 0.;
+    let mut games = 0;
+    for first in 0..WIDTH as i32 {
+        for second in 0..WIDTH as i32 {
+            for candidate_color in [Symbol::Red, Symbol::Yellow] {
+                let mut players = players(games);
+                let Some(outcome) = play_game::<WIDTH, HEIGHT>(
+                    winning_tokens,
+                    &mut players,
+                    candidate_color,
+                    [first, second],
+                ) else {
+                    continue;
+                };
+                points += match outcome.winner() {
+                    Some(winner) if winner == candidate_color => 1.,
+                    Some(_) => 0.,
+                    None => 0.5,
+                };
+                games += 1;
+            }
+        }
+    }
+    if games == 0 {
+        0.5
+    } else {
+        points / games as f64
+    }
+}
+
+/// Plays a game from `opening`, `players[0]` playing `candidate_color`.
+///
+/// Returns the outcome of the game, or `None` when the opening already ends it.
+fn play_game<const WIDTH: usize, const HEIGHT: usize>(
+    winning_tokens: i32,
+    players: &mut [Player<WIDTH, HEIGHT>; 2],
+    candidate_color: Symbol,
+    opening: [i32; 2],
+) -> Option<GameOutcome<Symbol>> {
+    let mut board = Connect4::<WIDTH, HEIGHT>::new(winning_tokens);
+    let mut symbol = Symbol::Red;
+    for column in opening {
+        if board.play_column(symbol, column)?.outcome.is_over() {
+            return None;
+        }
+        symbol = symbol.other();
+    }
+    loop {
+        let player = &mut players[if symbol == candidate_color { 0 } else { 1 }];
+        let next_move = player(&mut board, symbol)?;
+        if next_move.outcome.is_over() {
+            return Some(next_move.outcome);
+        }
+        symbol = symbol.other();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neighbours() {
+        let tuner = Tuner::new(4, 2);
+        let params = EvalParams {
+            token_weight: 1000,
+            empty_weight: 1,
+        };
+        assert_eq!(
+            vec![
+                EvalParams {
+                    token_weight: 1000,
+                    empty_weight: 2,
+                },
+                EvalParams {
+                    token_weight: 667,
+                    empty_weight: 1,
+                },
+            ],
+            tuner.neighbours(params)
+        );
+    }
+
+    #[test]
+    fn tune() {
+        let mut tuner = Tuner::new(3, 2);
+        let params = EvalParams::default();
+        assert_eq!(0.5, tuner.play_match::<5, 4>(params, params));
+
+        let mut rounds = 0;
+        let tuned = tuner.tune::<5, 4>(params, 2, |round| {
+            rounds += 1;
+            assert!(round.score.is_none_or(|score| score > 0.5));
+        });
+        assert_eq!(2, rounds);
+        assert!((MIN_WEIGHT..=MAX_WEIGHT).contains(&tuned.token_weight));
+        assert!((MIN_WEIGHT..=MAX_WEIGHT).contains(&tuned.empty_weight));
+    }
+
+    #[test]
+    fn max_weights() {
+        // Lines scoring more than the alpha-beta window holds.
+        let params = EvalParams {
+            token_weight: MAX_WEIGHT,
+            empty_weight: MIN_WEIGHT,
+        };
+        let mut board = Connect4::<7, 6>::new(4);
+        let mut symbol = Symbol::Red;
+        for column in [3, 3, 2, 4, 4, 2, 1, 5, 5] {
+            for bitboard in [false, true] {
+                let mut engine = Engine::new(TABLE_MEMORY);
+                engine.params = params;
+                engine.bitboard = bitboard;
+                assert!(board.search(&mut engine, symbol, 4).is_some());
+            }
+            board.play_column(symbol, column);
+            symbol = symbol.other();
+        }
+    }
+}
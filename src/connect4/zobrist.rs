@@ -0,0 +1,47 @@
+use crate::shared::coord::point::Point;
+use crate::shared::rng::splitmix64;
+
+use super::symbol::Symbol;
+
+/// Random key of `symbol` placed at `position`.
+///
+/// The hash of a board is the xor of the keys of all its tokens, so that it can be updated
+/// incrementally when a token is played or removed.
+pub fn key(position: Point, symbol: Symbol) -> u64 {
+    splitmix64((position.x as u64) << 32 | (position.y as u64) << 8 | symbol as u64)
+}
+
+/// Random key of `symbol` placed at the mirror image of `position` on a board of `width`
+/// columns, the first column becoming the last one.
+pub fn mirror_key(width: i32, position: Point, symbol: Symbol) -> u64 {
+    key(Point::new(width - 1 - position.x, position.y), symbol)
+}
+
+/// Random key of the player to move, xor-ed with the hash of the board.
+pub fn side_key(symbol: Symbol) -> u64 {
+    match symbol {
+        Symbol::Yellow => splitmix64(u64::MAX),
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys = HashSet::new();
+        for x in 0..16 {
+            for y in 0..16 {
+                for symbol in [Symbol::Red, Symbol::Yellow] {
+                    assert!(keys.insert(key(Point::new(x, y), symbol)));
+                }
+            }
+        }
+        assert!(keys.insert(side_key(Symbol::Yellow)));
+        assert_eq!(0, side_key(Symbol::Red));
+    }
+}
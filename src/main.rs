@@ -1,4 +1,19 @@
+use std::time::Duration;
+
+use connect4::play::analyze_connect4_position;
+use connect4::play::build_connect4_book;
+use connect4::play::calibrate_connect4_levels;
+use connect4::play::export_connect4_games;
+use connect4::play::generate_connect4_puzzles;
 use connect4::play::play_connect4;
+use connect4::play::run_connect4_protocol;
+use connect4::play::solve_connect4_puzzles;
+use connect4::play::train_connect4_evaluator;
+use connect4::play::tune_connect4;
+use connect4::play::Level;
+use connect4::play::MctsBudget;
+use connect4::play::Personality;
+use connect4::play::Thinking;
 use tictactoe::play::play_tictactoe;
 
 mod connect4;
@@ -7,12 +22,34 @@ mod shared;
 mod tictactoe;
 
 fn main() {
-    let difficulty = 1;
     let winning_tokens = 4;
     loop {
         println!("Type:");
         println!("- 't' for TicTacToe");
-        println!("- 'c' for Connect4");
+        println!(
+            "- 'c' for Connect4, the computer playing at level '{}'",
+            Level::default()
+        );
+        println!("- 'c <level>' for Connect4, the computer playing at 'beginner', 'novice',");
+        println!("  'intermediate', 'advanced', 'expert' or 'perfect' level");
+        println!("- 'c <seconds>' for Connect4, the computer thinking <seconds> per move");
+        println!("- 'c <seconds> <threads>' for the same, on <threads> threads");
+        println!("- 'r <personality> [<seed>]' for Connect4, the computer choosing its moves as");
+        println!("  'best', 'near=<margin>', 'softmax=<temperature>' or 'blunder=<rate>'");
+        println!("- 'm <playouts>' for Connect4, the computer running <playouts> random games");
+        println!("- 'm <seconds>s' for the same, running random games for <seconds> per move");
+        println!("- 'b <plies> <depth>' to build the Connect4 opening book");
+        println!("- 'e <rounds> <depth>' to tune the Connect4 evaluation by self-play");
+        println!("- 'g <count> <moves>' to generate Connect4 puzzles, win in <moves> moves");
+        println!("- 'z' to solve the Connect4 puzzles");
+        println!("- 'x <games> <depth> <random%>' to export Connect4 games of self-play");
+        println!("- 'l <epochs> <depth>' to train a Connect4 evaluation on the exported games");
+        println!("- 'o <position>' to analyze a Connect4 position, written as moves like 4453321");
+        println!("  or as a grid like 7/7/7/7/3Y3/2RR3 Y");
+        println!("- 'k' to calibrate the Connect4 levels by matches between them");
+        println!(
+            "- 'uci' to drive Connect4 from a GUI or another engine, with a UCI-like protocol"
+        );
         println!("- 'p' for PuzzleSolver");
         let line = {
             let mut line = String::new();
@@ -22,9 +59,91 @@ fn main() {
         if line == "t" {
             play_tictactoe().unwrap();
             continue;
-        } else if line == "c" {
-            play_connect4(difficulty, winning_tokens).unwrap();
+        } else if let Some(level) = line
+            .strip_prefix('c')
+            .filter(|level| level.is_empty() || level.starts_with(' '))
+            .and_then(parse_level)
+        {
+            play_connect4(
+                Thinking::Level(level),
+                winning_tokens,
+                1,
+                level.personality(),
+                shared::rng::clock_seed(),
+            )
+            .unwrap();
+            continue;
+        } else if let Some((seconds, threads)) = line.strip_prefix("c ").and_then(parse_time) {
+            let thinking = Thinking::Time(seconds);
+            play_connect4(thinking, winning_tokens, threads, Personality::Best, 0).unwrap();
+            continue;
+        } else if let Some((personality, seed)) =
+            line.strip_prefix("r ").and_then(parse_personality)
+        {
+            let thinking = Thinking::Level(Level::default());
+            play_connect4(thinking, winning_tokens, 1, personality, seed).unwrap();
+            continue;
+        } else if let Some([playouts]) = line.strip_prefix("m ").and_then(parse_numbers) {
+            let thinking = Thinking::Mcts(MctsBudget::Playouts(playouts.max(1) as u32));
+            play_connect4(thinking, winning_tokens, 1, Personality::Best, 0).unwrap();
+            continue;
+        } else if let Some(seconds) = line
+            .strip_prefix("m ")
+            .and_then(|line| line.strip_suffix('s'))
+            .and_then(parse_seconds)
+        {
+            let budget = MctsBudget::Time(seconds);
+            play_connect4(
+                Thinking::Mcts(budget),
+                winning_tokens,
+                1,
+                Personality::Best,
+                0,
+            )
+            .unwrap();
+            continue;
+        } else if let Some([plies, depth]) = line.strip_prefix("b ").and_then(parse_numbers) {
+            build_connect4_book(plies, depth, winning_tokens).unwrap();
+            continue;
+        } else if let Some([rounds, depth]) = line.strip_prefix("e ").and_then(parse_numbers) {
+            tune_connect4(rounds.max(0) as usize, depth, winning_tokens).unwrap();
+            continue;
+        } else if let Some([count, win_in]) = line.strip_prefix("g ").and_then(parse_numbers) {
+            generate_connect4_puzzles(
+                count.max(0) as usize,
+                win_in.clamp(1, (7 * 6 + 1) / 2),
+                winning_tokens,
+            )
+            .unwrap();
+            continue;
+        } else if line == "z" {
+            solve_connect4_puzzles(winning_tokens).unwrap();
+            continue;
+        } else if let Some([games, depth, randomness]) =
+            line.strip_prefix("x ").and_then(parse_numbers)
+        {
+            export_connect4_games(
+                games.max(0) as usize,
+                depth,
+                randomness.clamp(0, 100) as u32,
+                winning_tokens,
+            )
+            .unwrap();
+            continue;
+        } else if let Some([epochs, depth]) = line.strip_prefix("l ").and_then(parse_numbers) {
+            train_connect4_evaluator(epochs.max(0) as usize, depth, winning_tokens).unwrap();
+            continue;
+        } else if let Some(position) = line.strip_prefix("o ") {
+            if let Err(err) = analyze_connect4_position(position.trim(), winning_tokens) {
+                println!("{err}");
+            }
+            continue;
+        } else if line == "k" {
+            calibrate_connect4_levels(winning_tokens).unwrap();
             continue;
+        } else if line == "uci" {
+            run_connect4_protocol().unwrap();
+            return;
         } else if line == "p" {
             puzzlesolver::solve_puzzle();
             continue;
@@ -36,3 +155,59 @@ fn main() {
         }
     }
 }
+
+/// Parses a thinking time in seconds, optionally followed by a number of threads, all the
+/// available cores by default.
+fn parse_time(line: &str) -> Option<(Duration, usize)> {
+    let mut words = line.split_whitespace();
+    let seconds = parse_seconds(words.next()?)?;
+    let threads = match words.next() {
+        Some(threads) => threads
+            .parse::<usize>()
+            .ok()
+            .filter(|threads| *threads > 0)?,
+        None => std::thread::available_parallelism().map_or(1, usize::from),
+    };
+    words.next().is_none().then_some((seconds, threads))
+}
+
+/// Parses a duration in seconds, or returns `None` if it is negative, not a number or too
+/// long to represent.
+fn parse_seconds(seconds: &str) -> Option<Duration> {
+    Duration::try_from_secs_f64(seconds.trim().parse().ok()?).ok()
+}
+
+/// Parses a level, the default one if there is none.
+fn parse_level(line: &str) -> Option<Level> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Some(Level::default());
+    }
+    line.parse().ok()
+}
+
+/// Parses a personality, optionally followed by a seed, from the clock by default.
+fn parse_personality(line: &str) -> Option<(Personality, u64)> {
+    let mut words = line.split_whitespace();
+    let personality = match words.next()?.parse() {
+        Ok(personality) => personality,
+        Err(err) => {
+            println!("{err}");
+            return None;
+        }
+    };
+    let seed = match words.next() {
+        Some(seed) => seed.parse().ok()?,
+        None => shared::rng::clock_seed(),
+    };
+    words.next().is_none().then_some((personality, seed))
+}
+
+/// Parses `N` space-separated numbers.
+fn parse_numbers<const N: usize>(line: &str) -> Option<[i32; N]> {
+    let numbers = line
+        .split_whitespace()
+        .map(|number| number.parse().ok())
+        .collect::<Option<Vec<i32>>>()?;
+    numbers.try_into().ok()
+}
@@ -0,0 +1,28 @@
+use crate::shared::coord::point::Point;
+
+/// State of a game once a move is played, for the players of type `Symbol`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameOutcome<Symbol> {
+    /// The game goes on.
+    Ongoing,
+
+    /// `winner` completed a line, given by its first and last cells.
+    Win { winner: Symbol, line: [Point; 2] },
+
+    /// The board is full and nobody won.
+    Draw,
+}
+
+impl<Symbol: Copy> GameOutcome<Symbol> {
+    /// Whether the game ended, won or drawn.
+    pub fn is_over(&self) -> bool {
+        !matches!(self, GameOutcome::Ongoing)
+    }
+
+    pub fn winner(&self) -> Option<Symbol> {
+        match self {
+            GameOutcome::Win { winner, .. } => Some(*winner),
+            _ => None,
+        }
+    }
+}
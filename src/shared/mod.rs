@@ -1,2 +1,4 @@
 pub mod board;
 pub mod coord;
+pub mod game_outcome;
+pub mod rng;
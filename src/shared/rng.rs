@@ -0,0 +1,83 @@
+/// Scrambles the bits of `seed`, see <https://prng.di.unimi.it/splitmix64.c>.
+///
+/// Distinct seeds give distinct results.
+pub fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+/// A seed that differs from one run to the next, from the clock.
+pub fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_nanos() as u64)
+}
+
+/// Small pseudo-random number generator, scrambling a counter with `splitmix64`.
+///
+/// The same seed always gives the same numbers, so that random games can be replayed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(1);
+        splitmix64(self.state)
+    }
+
+    /// Random number in `0..len`, which must not be empty.
+    pub fn below(&mut self, len: usize) -> usize {
+        (self.next_u64() % len as u64) as usize
+    }
+
+    /// Random number in `0..1`.
+    pub fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Random item of `items`, or `None` if there are none.
+    pub fn choose<'t, T>(&mut self, items: &'t [T]) -> Option<&'t T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len())])
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed() {
+        let numbers = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..10).map(|_| rng.next_u64()).collect::<Vec<_>>()
+        };
+        assert_eq!(numbers(7), numbers(7));
+        assert_ne!(numbers(7), numbers(8));
+        assert_eq!(splitmix64(8), numbers(7)[0]);
+    }
+
+    #[test]
+    fn ranges() {
+        let mut rng = Rng::new(0);
+        let mut seen = [false; 6];
+        for _ in 0..1000 {
+            seen[rng.below(6)] = true;
+            assert!((0. ..1.).contains(&rng.unit()));
+        }
+        assert!(seen.iter().all(|seen| *seen));
+        assert_eq!(None, rng.choose::<i32>(&[]));
+        assert_eq!(Some(&3), rng.choose(&[3]));
+    }
+}
@@ -5,17 +5,19 @@ use lazy_static::lazy_static;
 
 use regex::Regex;
 
-use crate::shared::board::board_all_points::BoardAllPoints;
 use crate::shared::board::board_generate::BoardGenerate;
-use crate::shared::board::board_get_set::BoardGet;
 use crate::shared::board::board_get_set::BoardSet;
 use crate::shared::board::board_size::BoardSize;
 use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
 use crate::tictactoe::board::Board;
 use crate::tictactoe::tictactoe::Symbol;
 use crate::tictactoe::tictactoe::TicTacToeBoard;
 
-pub fn play_tictactoe() -> Result<(), Error> {
+/// Plays TicTacToe against the computer.
+///
+/// Returns the outcome of the game, `Ongoing` if the input ended first.
+pub fn play_tictactoe() -> Result<GameOutcome<Symbol>, Error> {
     println!("START");
 
     let me = Symbol::Circle;
@@ -27,7 +29,7 @@ pub fn play_tictactoe() -> Result<(), Error> {
 
     let lines = std::io::stdin().lines();
     for line in lines {
-        let run_round = || -> Result<(), Error> {
+        let run_round = || -> Result<GameOutcome<Symbol>, Error> {
             let line = line?.to_ascii_uppercase();
 
             lazy_static! {
@@ -68,12 +70,13 @@ pub fn play_tictactoe() -> Result<(), Error> {
             println!("Board is now:");
             println!("{}", board);
             println!("");
-            if board.is_winning_move(point, me) {
-                println!("{:?} WINS !!!", me);
-                return Err(Error::EOG);
+            let outcome = board.outcome(point, me);
+            if outcome.is_over() {
+                return Ok(outcome);
             }
 
-            let next = board.next_move(me.other()).ok_or(Error::EOG)?;
+            // The board isn't full, so there is a next move.
+            let next = board.next_move(me.other()).unwrap();
             *board.get_mut(&next.point()) = me.other();
 
             println!(
@@ -86,32 +89,23 @@ pub fn play_tictactoe() -> Result<(), Error> {
             println!("{}", board);
             println!("");
 
-            if board.is_winning_move(next.point(), me.other()) {
-                println!("{:?} WINS !!!", me.other());
-                return Err(Error::EOG);
-            }
-
-            if board
-                .all_points()
-                .map(|point| *board.get(&point))
-                .all(|v| v != Symbol::None)
-            {
-                return Err(Error::EOG);
-            }
-
-            Ok(())
+            Ok(board.outcome(next.point(), me.other()))
         };
 
         match run_round() {
-            Ok(_) => (),
-            Err(Error::EOG) => {
+            Ok(GameOutcome::Ongoing) => (),
+            Ok(outcome) => {
+                match outcome {
+                    GameOutcome::Win { winner, .. } => println!("{:?} WINS !!!", winner),
+                    _ => println!("DRAW"),
+                }
                 println!("End of game");
-                return Ok(());
+                return Ok(outcome);
             }
             Err(err) => println!("Failed with {err}"),
         }
     }
-    Ok(())
+    Ok(GameOutcome::Ongoing)
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -128,9 +122,6 @@ pub enum Error {
     #[error("Unable to parse the column number")]
     ParseColumnError(ParseCharError),
 
-    #[error("End of the game")]
-    EOG,
-
     #[error("Point is out of the board: {:?}", .point)]
     InvalidPoint { point: Point },
 }
@@ -9,6 +9,7 @@ use crate::shared::board::board_size::BoardSize;
 use crate::shared::board::board_to_string::AsChar;
 use crate::shared::coord::directions;
 use crate::shared::coord::point::Point;
+use crate::shared::game_outcome::GameOutcome;
 use crate::tictactoe::board::Board;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
@@ -50,6 +51,9 @@ pub trait TicTacToeBoard {
     fn eval(&self, symbol: Symbol) -> i32;
     fn next_move(&mut self, symbol: Symbol) -> Option<Game>;
     fn is_winning_move(&self, point: Point, symbol: Symbol) -> bool;
+
+    /// Outcome of the game once `symbol` played at `point`.
+    fn outcome(&self, point: Point, symbol: Symbol) -> GameOutcome<Symbol>;
 }
 
 impl TicTacToeBoard for Board {
@@ -145,6 +149,24 @@ impl TicTacToeBoard for Board {
         }
         return false;
     }
+
+    fn outcome(&self, point: Point, symbol: Symbol) -> GameOutcome<Symbol> {
+        if let Some(line) = LINES.iter().find(|line| {
+            line.contains(&point) && line.iter().all(|point| *self.get(point) == symbol)
+        }) {
+            GameOutcome::Win {
+                winner: symbol,
+                line: [line[0], line[2]],
+            }
+        } else if self
+            .all_points()
+            .all(|point| *self.get(&point) != Symbol::None)
+        {
+            GameOutcome::Draw
+        } else {
+            GameOutcome::Ongoing
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -186,4 +208,46 @@ mod tests {
         board.set(&Point::new(2, 2), Symbol::Circle);
         assert_eq!(1000, board.eval(Symbol::Circle));
     }
+
+    #[test]
+    fn outcome() {
+        let mut board = Board::generate(|_| Symbol::None);
+        board.set(&Point::new(0, 0), Symbol::Circle);
+        assert_eq!(
+            GameOutcome::Ongoing,
+            board.outcome(Point::new(0, 0), Symbol::Circle)
+        );
+
+        board.set(&Point::new(1, 1), Symbol::Circle);
+        board.set(&Point::new(2, 2), Symbol::Circle);
+        assert_eq!(
+            GameOutcome::Win {
+                winner: Symbol::Circle,
+                line: [Point::new(0, 0), Point::new(2, 2)],
+            },
+            board.outcome(Point::new(1, 1), Symbol::Circle)
+        );
+
+        // O X O
+        // O X X
+        // X O O
+        let mut board = Board::generate(|_| Symbol::None);
+        for (x, y, symbol) in [
+            (0, 0, Symbol::Circle),
+            (1, 0, Symbol::Cross),
+            (2, 0, Symbol::Circle),
+            (0, 1, Symbol::Circle),
+            (1, 1, Symbol::Cross),
+            (2, 1, Symbol::Cross),
+            (0, 2, Symbol::Cross),
+            (1, 2, Symbol::Circle),
+            (2, 2, Symbol::Circle),
+        ] {
+            board.set(&Point::new(x, y), symbol);
+        }
+        assert_eq!(
+            GameOutcome::Draw,
+            board.outcome(Point::new(2, 2), Symbol::Circle)
+        );
+    }
 }